@@ -0,0 +1,38 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use day6::{find_marker_pos, find_marker_pos_bitmask, find_marker_pos_parallel};
+
+// A datastream with its only start-of-message marker at the very end.
+fn datastream(len: usize) -> Vec<u8> {
+    let marker = b"abcdefghijklmn";
+    let mut datastream = b"abcdefghijklm"
+        .iter()
+        .copied()
+        .cycle()
+        .take(len - marker.len())
+        .collect::<Vec<_>>();
+    datastream.extend_from_slice(marker);
+    datastream
+}
+
+fn bench_marker(c: &mut Criterion) {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut group = c.benchmark_group("marker");
+    for len in [1 << 20, 64 << 20] {
+        let input = datastream(len);
+        group.throughput(Throughput::Bytes(len as u64));
+        group.bench_with_input(BenchmarkId::new("last_seen", len), &input, |b, input| {
+            b.iter(|| find_marker_pos(black_box(input), 14))
+        });
+        group.bench_with_input(BenchmarkId::new("bitmask", len), &input, |b, input| {
+            b.iter(|| find_marker_pos_bitmask(black_box(input), 14))
+        });
+        group.bench_with_input(BenchmarkId::new("parallel", len), &input, |b, input| {
+            b.iter(|| find_marker_pos_parallel(black_box(input), 14, threads))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_marker);
+criterion_main!(benches);
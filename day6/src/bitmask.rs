@@ -0,0 +1,159 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+// Toggles the bit of a byte in a 256-bit mask and returns the change in the
+// number of set bits.
+#[inline(always)]
+fn toggle_byte(mask: &mut [u64; 4], byte: u8) -> i32 {
+    let word = &mut mask[usize::from(byte >> 6)];
+    let shift = byte & 63;
+    let was_set = ((*word >> shift) & 1) as i32;
+    *word ^= 1 << shift;
+    1 - 2 * was_set
+}
+
+/// Same as [`crate::find_marker_pos`], using a rolling XOR of byte bits over
+/// the window.
+///
+/// A window is a marker when its mask has one bit set per byte, since any
+/// duplicated byte cancels itself out.
+pub fn find_marker_pos_bitmask(datastream: &[u8], marker_len: usize) -> Option<usize> {
+    if marker_len == 0 {
+        return Some(0);
+    }
+    scan(datastream, marker_len, || false).map(|pos| pos + 1)
+}
+
+// Returns the index of the last byte of the first marker, or `None` if
+// `stop` returns true, which is only checked every few kilobytes.
+#[inline(always)]
+fn scan(datastream: &[u8], marker_len: usize, stop: impl Fn() -> bool) -> Option<usize> {
+    const STOP_CHECK_LEN: usize = 16 * 1024;
+
+    if marker_len > 256 || datastream.len() < marker_len {
+        return None;
+    }
+    let marker_len_i32 = marker_len as i32;
+    let mut mask = [0; 4];
+    // Number of bits set in the mask, kept up to date to avoid popcounts.
+    let mut ones = 0;
+    for &byte in &datastream[..marker_len - 1] {
+        ones += toggle_byte(&mut mask, byte);
+    }
+    for end in marker_len - 1..datastream.len() {
+        if end.is_multiple_of(STOP_CHECK_LEN) && stop() {
+            return None;
+        }
+        ones += toggle_byte(&mut mask, datastream[end]);
+        if ones == marker_len_i32 {
+            return Some(end);
+        }
+        ones += toggle_byte(&mut mask, datastream[end + 1 - marker_len]);
+    }
+    None
+}
+
+/// Same as [`crate::find_marker_pos`], splitting the datastream into
+/// `threads` chunks scanned in parallel like [`find_marker_pos_bitmask`].
+///
+/// Each chunk also scans the `marker_len - 1` bytes before it so markers
+/// straddling a boundary are found by the chunk holding their last byte.
+/// Chunks stop scanning once an earlier one has found a marker.
+pub fn find_marker_pos_parallel(
+    datastream: &[u8],
+    marker_len: usize,
+    threads: usize,
+) -> Option<usize> {
+    // Below this, spawning threads costs more than it saves.
+    const MIN_CHUNK_LEN: usize = 64 * 1024;
+
+    if marker_len == 0 {
+        return Some(0);
+    }
+    let threads = threads.min(datastream.len() / MIN_CHUNK_LEN).max(1);
+    if threads == 1 {
+        return find_marker_pos_bitmask(datastream, marker_len);
+    }
+    let chunk_len = datastream.len().div_ceil(threads);
+    // Index of the first chunk known to hold a marker.
+    let earliest = AtomicUsize::new(usize::MAX);
+    thread::scope(|s| {
+        let handles = (0..datastream.len())
+            .step_by(chunk_len)
+            .enumerate()
+            .map(|(i, chunk_start)| {
+                let scan_start = chunk_start.saturating_sub(marker_len - 1);
+                let chunk_end = (chunk_start + chunk_len).min(datastream.len());
+                let chunk = &datastream[scan_start..chunk_end];
+                let earliest = &earliest;
+                s.spawn(move || {
+                    let end = scan(chunk, marker_len, || earliest.load(Ordering::Relaxed) < i)?;
+                    earliest.fetch_min(i, Ordering::Relaxed);
+                    Some(scan_start + end + 1)
+                })
+            })
+            .collect::<Vec<_>>();
+        // Chunks are in order so the first one with a marker wins.
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("marker scanning thread"))
+            .find_map(|pos| pos)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::find_marker_pos;
+
+    #[test]
+    fn test_find_marker_pos_bitmask() {
+        for datastream in [
+            "mjqjpqmgbljsphdztnvjfqwrcgsmlb",
+            "bvwbjplbgvbhsrlpgdmjqwftvncz",
+            "nppdvjthqldpwncqszvftbrmjlhg",
+            "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg",
+            "zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw",
+            "abcabc",
+            "A!a\x01",
+            "ab\ncd\njk",
+        ] {
+            for marker_len in [0, 1, 4, 14, 27] {
+                assert_eq!(
+                    find_marker_pos_bitmask(datastream.as_bytes(), marker_len),
+                    find_marker_pos(datastream.as_bytes(), marker_len),
+                    "{datastream:?} with marker_len={marker_len}"
+                );
+            }
+        }
+        let alphabet = (0..=255).collect::<Vec<u8>>();
+        assert_eq!(find_marker_pos_bitmask(&alphabet, 256), Some(256));
+        assert_eq!(find_marker_pos_bitmask(&alphabet, 257), None);
+    }
+
+    #[test]
+    fn test_find_marker_pos_parallel() {
+        // Markers only exist around the boundary between the first two chunks.
+        let mut datastream = b"abcabc".repeat(50_000);
+        let marker = b"defghijklmnopq";
+        let boundary = datastream.len() / 2;
+        datastream.splice(boundary - 5..boundary - 5 + marker.len(), *marker);
+        let expected = find_marker_pos(&datastream, 14);
+        assert!(expected.is_some_and(|pos| pos - 14 < boundary && pos > boundary));
+        for threads in 1..=8 {
+            assert_eq!(find_marker_pos_parallel(&datastream, 14, threads), expected);
+        }
+        assert_eq!(find_marker_pos_parallel(&b"ab".repeat(200_000), 4, 4), None);
+        // Any byte can be part of a marker, and an early one stops the others.
+        let mut datastream = b"\n\n".repeat(500_000);
+        datastream[1000..1004].copy_from_slice(b"\0\n\xffA");
+        for threads in 1..=8 {
+            assert_eq!(
+                find_marker_pos_parallel(&datastream, 4, threads),
+                Some(1004)
+            );
+        }
+    }
+}
@@ -1,38 +1,148 @@
-use std::collections::VecDeque;
-
-fn find_marker_pos(datastream: &str, marker_len: usize) -> usize {
-    let mut queue = VecDeque::with_capacity(4);
-    for (pos, c) in datastream.char_indices() {
-        // Assume there is always is a marker.
-        if queue.len() == marker_len {
-            // It means we found a marker in the previous loop.
-            // The result is previous loop char pos + 1 OR this char pos.
-            return pos;
+use std::io::{self, Read};
+
+pub mod bitmask;
+pub use bitmask::*;
+
+/// Tracks the longest all-distinct window ending at the last pushed byte.
+struct MarkerScanner {
+    marker_len: usize,
+    // Position + 1 of the last occurrence of each byte, 0 when unseen.
+    last_seen: [usize; 256],
+    window_start: usize,
+    pos: usize,
+}
+
+impl MarkerScanner {
+    fn new(marker_len: usize) -> Self {
+        Self {
+            marker_len,
+            last_seen: [0; 256],
+            window_start: 0,
+            pos: 0,
+        }
+    }
+
+    /// Pushes the next byte and returns the marker end position if the
+    /// `marker_len` bytes ending with it are all different.
+    #[inline(always)]
+    fn push(&mut self, byte: u8) -> Option<usize> {
+        let seen = &mut self.last_seen[byte as usize];
+        // Shrink the window past the previous occurrence of this byte.
+        self.window_start = self.window_start.max(*seen);
+        self.pos += 1;
+        *seen = self.pos;
+        (self.pos - self.window_start >= self.marker_len).then_some(self.pos)
+    }
+
+    /// Forgets the current window so the next marker can't share bytes with it.
+    #[inline(always)]
+    fn restart_window(&mut self) {
+        self.window_start = self.pos;
+    }
+}
+
+/// A window of all-different bytes found in a datastream.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Marker<'a> {
+    /// Number of bytes processed once the marker is complete.
+    pub pos: usize,
+    pub marker: &'a [u8],
+}
+
+/// Iterator over every marker of a datastream, see [`find_markers`].
+pub struct Markers<'a> {
+    datastream: &'a [u8],
+    scanner: MarkerScanner,
+    non_overlapping: bool,
+}
+
+impl<'a> Markers<'a> {
+    /// Only yield markers that don't share any byte with the previous one.
+    pub fn non_overlapping(mut self) -> Self {
+        self.non_overlapping = true;
+        self
+    }
+}
+
+impl<'a> Iterator for Markers<'a> {
+    type Item = Marker<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let marker_len = self.scanner.marker_len;
+        if marker_len == 0 {
+            // Every position ends an empty marker, the first one included.
+            let pos = self.scanner.pos;
+            if pos > self.datastream.len() {
+                return None;
+            }
+            self.scanner.pos += 1;
+            return Some(Marker { pos, marker: &[] });
         }
-        // Did not find a marker previously.
-
-        // Check if it already exists in the marker sequence.
-        if queue.contains(&c) {
-            // Pop the front chars until the duplicate char.
-            loop {
-                let front_char = queue.pop_front().expect("safe");
-                if front_char == c {
-                    break;
+        while let Some(&byte) = self.datastream.get(self.scanner.pos) {
+            if let Some(pos) = self.scanner.push(byte) {
+                if self.non_overlapping {
+                    self.scanner.restart_window();
                 }
+                return Some(Marker {
+                    pos,
+                    marker: &self.datastream[pos - marker_len..pos],
+                });
             }
         }
-        // Push the char to the end of the queue.
-        queue.push_back(c);
+        None
+    }
+}
+
+/// Returns an iterator over every position where the last `marker_len` bytes
+/// are all different, in increasing order. Like [`find_marker_pos`], an empty
+/// marker ends at every position, starting from 0.
+pub fn find_markers(datastream: &[u8], marker_len: usize) -> Markers<'_> {
+    Markers {
+        datastream,
+        scanner: MarkerScanner::new(marker_len),
+        non_overlapping: false,
+    }
+}
+
+/// Returns the number of bytes processed once the last `marker_len` bytes are
+/// all different, or `None` if the datastream has no such marker.
+pub fn find_marker_pos(datastream: &[u8], marker_len: usize) -> Option<usize> {
+    if marker_len == 0 {
+        return Some(0);
+    }
+    let mut scanner = MarkerScanner::new(marker_len);
+    datastream.iter().find_map(|&byte| scanner.push(byte))
+}
+
+/// Same as [`find_marker_pos`] but scans a stream chunk by chunk.
+pub fn find_marker_pos_in_reader(
+    mut reader: impl Read,
+    marker_len: usize,
+) -> io::Result<Option<usize>> {
+    if marker_len == 0 {
+        return Ok(Some(0));
+    }
+    let mut scanner = MarkerScanner::new(marker_len);
+    let mut buffer = [0; 8 * 1024];
+    loop {
+        let bread = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(None),
+            Ok(bread) => bread,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if let Some(pos) = buffer[..bread].iter().find_map(|&byte| scanner.push(byte)) {
+            return Ok(Some(pos));
+        }
     }
-    panic!("impossible")
 }
 
 pub fn solve_part1(datastream: &str) -> usize {
-    find_marker_pos(datastream, 4)
+    find_marker_pos(datastream.as_bytes(), 4).expect("start-of-packet marker")
 }
 
 pub fn solve_part2(datastream: &str) -> usize {
-    find_marker_pos(datastream, 14)
+    find_marker_pos(datastream.as_bytes(), 14).expect("start-of-message marker")
 }
 
 #[cfg(test)]
@@ -56,4 +166,66 @@ mod tests {
         assert_eq!(solve_part2("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg"), 29);
         assert_eq!(solve_part2("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw"), 26);
     }
+
+    #[test]
+    fn test_find_marker_pos() {
+        assert_eq!(find_marker_pos(b"abcabc", 0), Some(0));
+        assert_eq!(find_marker_pos(b"aaaa", 1), Some(1));
+        assert_eq!(find_marker_pos(b"aabbcc", 2), Some(3));
+        assert_eq!(find_marker_pos(b"abcabc", 4), None);
+        assert_eq!(find_marker_pos(b"", 4), None);
+        let alphabet = (0..=255).collect::<Vec<u8>>();
+        assert_eq!(find_marker_pos(&alphabet, 256), Some(256));
+        assert_eq!(find_marker_pos(&alphabet, 257), None);
+    }
+
+    #[test]
+    fn test_find_marker_pos_in_reader() {
+        let datastream = "zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw";
+        assert_eq!(
+            find_marker_pos_in_reader(datastream.as_bytes(), 14).unwrap(),
+            Some(26)
+        );
+        assert_eq!(
+            find_marker_pos_in_reader("abcabc".as_bytes(), 4).unwrap(),
+            None
+        );
+        // A marker spanning two chunks.
+        let mut long = vec![b'a'; 8 * 1024 - 2];
+        long.extend_from_slice(b"bcd");
+        assert_eq!(
+            find_marker_pos_in_reader(long.as_slice(), 4).unwrap(),
+            Some(long.len())
+        );
+    }
+
+    #[test]
+    fn test_find_markers() {
+        let markers = find_markers(b"abcabcc", 3)
+            .map(|m| (m.pos, m.marker))
+            .collect::<Vec<_>>();
+        let expected: [(usize, &[u8]); 4] = [(3, b"abc"), (4, b"bca"), (5, b"cab"), (6, b"abc")];
+        assert_eq!(markers, expected);
+        let positions = find_markers(b"abcabcc", 3)
+            .non_overlapping()
+            .map(|m| m.pos)
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![3, 6]);
+        assert_eq!(
+            find_markers(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14)
+                .next()
+                .map(|m| m.pos),
+            find_marker_pos(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14)
+        );
+        assert_eq!(find_markers(b"aaaa", 2).count(), 0);
+
+        let empty = find_markers(b"aab", 0).collect::<Vec<_>>();
+        assert_eq!(
+            empty.iter().map(|m| m.pos).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        assert!(empty.iter().all(|m| m.marker.is_empty()));
+        assert_eq!(empty[0].pos, find_marker_pos(b"aab", 0).unwrap());
+        assert_eq!(find_markers(b"aab", 0).non_overlapping().count(), 4);
+    }
 }
@@ -0,0 +1,103 @@
+use std::{
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+
+use crate::{Filesystem, NodeId, NodeKind};
+
+impl<'a> Filesystem<'a> {
+    /// Recreates the tree under `target`, files being sparse files of their
+    /// recorded size.
+    pub fn materialize(&self, target: &Path) -> io::Result<()> {
+        fs::create_dir_all(target)?;
+        self.materialize_children(self.root(), target)
+    }
+
+    fn materialize_children(&self, dir: NodeId, dir_path: &Path) -> io::Result<()> {
+        self.node(dir).children().iter().try_for_each(|&child| {
+            let node = self.node(child);
+            let path = dir_path.join(checked_name(node.name())?);
+            match node.kind() {
+                NodeKind::Directory { .. } => {
+                    fs::create_dir_all(&path)?;
+                    self.materialize_children(child, &path)
+                }
+                // Extending an empty file leaves a hole so no block is written.
+                NodeKind::File { size } => fs::File::create(&path)?.set_len(*size as u64),
+            }
+        })
+    }
+}
+
+// Names come from a transcript, make sure they can't escape the target.
+fn checked_name(name: &str) -> io::Result<&str> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid file name {name:?}"),
+        ))
+    } else {
+        Ok(name)
+    }
+}
+
+// Lines are split on line breaks and trimmed when parsing a transcript.
+fn transcript_name<'a>(name: &'a str, path: &Path) -> io::Result<&'a str> {
+    if name.contains(['\n', '\r']) || name.trim() != name {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} can't be written in a transcript", path.display()),
+        ))
+    } else {
+        Ok(name)
+    }
+}
+
+/// Walks a real directory and returns the `$ cd`/`$ ls` transcript that
+/// would list it, entries being sorted by name.
+///
+/// Symbolic links and non UTF-8 names are skipped with a warning. Names that
+/// a transcript can't hold, with a line break or surrounding whitespace, are
+/// an [`io::ErrorKind::InvalidData`] error.
+pub fn transcript_from_dir(dir: &Path) -> io::Result<String> {
+    let mut transcript = format!("$ cd {}\n", Filesystem::ROOT_NAME);
+    write_dir_transcript(dir, &mut transcript)?;
+    Ok(transcript)
+}
+
+fn write_dir_transcript(dir: &Path, transcript: &mut String) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| {
+            let entry = entry?;
+            Ok((entry.file_name(), entry.path(), entry.metadata()?))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+
+    transcript.push_str("$ ls\n");
+    let mut sub_dirs: Vec<(String, PathBuf)> = Vec::new();
+    for (name, path, metadata) in entries {
+        let Some(name) = name.to_str() else {
+            warn!("skipping non UTF-8 name {}", path.display());
+            continue;
+        };
+        let name = transcript_name(name, &path)?;
+        if metadata.is_dir() {
+            writeln!(transcript, "dir {name}").expect("safe");
+            sub_dirs.push((name.to_string(), path));
+        } else if metadata.is_file() {
+            writeln!(transcript, "{} {name}", metadata.len()).expect("safe");
+        } else {
+            warn!("skipping {}, not a file nor a directory", path.display());
+        }
+    }
+    sub_dirs.iter().try_for_each(|(name, path)| {
+        writeln!(transcript, "$ cd {name}").expect("safe");
+        write_dir_transcript(path, transcript)?;
+        transcript.push_str("$ cd ..\n");
+        Ok(())
+    })
+}
@@ -0,0 +1,71 @@
+use std::fmt::{self, Display};
+
+use serde_json::{json, Value};
+
+use crate::{Filesystem, NodeId, NodeKind};
+
+impl<'a> Filesystem<'a> {
+    /// Nested JSON objects with the name, kind, size and total size of every
+    /// node. Directories have a `null` size and a `children` array.
+    pub fn to_json(&self) -> Value {
+        self.node_to_json(self.root())
+    }
+
+    fn node_to_json(&self, id: NodeId) -> Value {
+        let node = self.node(id);
+        match node.kind() {
+            NodeKind::File { size } => json!({
+                "name": node.name(),
+                "kind": "file",
+                "size": size,
+                "total_size": size,
+            }),
+            NodeKind::Directory { children, .. } => json!({
+                "name": node.name(),
+                "kind": "dir",
+                "size": null,
+                "total_size": node.size(),
+                "children": children
+                    .iter()
+                    .map(|&child| self.node_to_json(child))
+                    .collect::<Vec<_>>(),
+            }),
+        }
+    }
+
+    /// Graphviz graph of the tree, labels showing the cumulative sizes.
+    pub fn dot(&self) -> DotExport<'_, 'a> {
+        DotExport { fs: self }
+    }
+}
+
+/// Graphviz DOT rendering, see [`Filesystem::dot`].
+pub struct DotExport<'f, 'a> {
+    fs: &'f Filesystem<'a>,
+}
+
+impl<'f, 'a> Display for DotExport<'f, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "digraph filesystem {{")?;
+        writeln!(f, "    node [fontname=\"monospace\"];")?;
+        for (id, node) in self.fs.iter() {
+            let shape = if node.is_dir() { "folder" } else { "note" };
+            writeln!(
+                f,
+                "    n{} [shape={shape}, label=\"{}\\n{}\"];",
+                id.0,
+                escape(node.name()),
+                node.size()
+            )?;
+            if let Some(parent) = node.parent() {
+                writeln!(f, "    n{} -> n{};", parent.0, id.0)?;
+            }
+        }
+        write!(f, "}}")
+    }
+}
+
+// Escapes a name for a double quoted DOT string.
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
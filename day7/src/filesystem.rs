@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+/// Index of a node in its [`Filesystem`] arena.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub struct NodeId(pub(crate) usize);
+
+#[derive(Debug)]
+pub enum NodeKind<'a> {
+    Directory {
+        children: Vec<NodeId>,
+        by_name: HashMap<&'a str, NodeId>,
+        // Sum of the sizes of every file below this directory.
+        total_size: usize,
+    },
+    File {
+        size: usize,
+    },
+}
+
+#[derive(Debug)]
+pub struct Node<'a> {
+    name: &'a str,
+    parent: Option<NodeId>,
+    kind: NodeKind<'a>,
+}
+
+impl<'a> Node<'a> {
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn kind(&self) -> &NodeKind<'a> {
+        &self.kind
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self.kind, NodeKind::Directory { .. })
+    }
+
+    /// The file size, or the total size of a directory.
+    pub fn size(&self) -> usize {
+        use NodeKind::*;
+        match self.kind {
+            Directory { total_size, .. } => total_size,
+            File { size } => size,
+        }
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        use NodeKind::*;
+        match &self.kind {
+            Directory { children, .. } => children,
+            File { .. } => &[],
+        }
+    }
+}
+
+#[derive(Error, PartialEq, Eq, Debug)]
+pub enum FilesystemError {
+    #[error("{0} is not a directory")]
+    NotADirectory(String),
+    #[error("{0} is a directory")]
+    IsADirectory(String),
+    #[error("{path} was listed with size {recorded} then {listed}")]
+    InconsistentSize {
+        path: String,
+        recorded: usize,
+        listed: usize,
+    },
+}
+
+/// A filesystem tree stored in an arena, the root being its first node.
+#[derive(Debug)]
+pub struct Filesystem<'a> {
+    nodes: Vec<Node<'a>>,
+}
+
+impl<'a> Default for Filesystem<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Filesystem<'a> {
+    pub const ROOT_NAME: &'static str = "/";
+    const ROOT: NodeId = NodeId(0);
+
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                name: Self::ROOT_NAME,
+                parent: None,
+                kind: NodeKind::Directory {
+                    children: Vec::new(),
+                    by_name: HashMap::new(),
+                    total_size: 0,
+                },
+            }],
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        Self::ROOT
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node<'a> {
+        &self.nodes[id.0]
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn size(&self, id: NodeId) -> usize {
+        self.node(id).size()
+    }
+
+    /// Absolute path of a node, e.g. `/a/e/i`.
+    pub fn path(&self, id: NodeId) -> String {
+        let mut names = Vec::new();
+        let mut current = Some(id);
+        while let Some(id) = current {
+            names.push(self.node(id).name);
+            current = self.parent(id);
+        }
+        // Drop the root name, it is the leading separator.
+        names.pop();
+        if names.is_empty() {
+            return Self::ROOT_NAME.to_string();
+        }
+        names
+            .iter()
+            .rev()
+            .fold(String::new(), |path, name| path + Self::ROOT_NAME + name)
+    }
+
+    pub fn get_child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        use NodeKind::*;
+        match &self.node(dir).kind {
+            Directory { by_name, .. } => by_name.get(name).copied(),
+            File { .. } => None,
+        }
+    }
+
+    /// Returns the `name` directory inside `dir`, creating it if needed.
+    pub fn add_dir(&mut self, dir: NodeId, name: &'a str) -> Result<NodeId, FilesystemError> {
+        match self.get_child(dir, name) {
+            Some(child) if self.node(child).is_dir() => Ok(child),
+            Some(child) => Err(FilesystemError::NotADirectory(self.path(child))),
+            None => self.push_child(
+                dir,
+                name,
+                NodeKind::Directory {
+                    children: Vec::new(),
+                    by_name: HashMap::new(),
+                    total_size: 0,
+                },
+            ),
+        }
+    }
+
+    /// Returns the `name` file inside `dir`, creating it if needed.
+    ///
+    /// Fails if the file was already recorded with another size.
+    pub fn add_file(
+        &mut self,
+        dir: NodeId,
+        name: &'a str,
+        size: usize,
+    ) -> Result<NodeId, FilesystemError> {
+        if let Some(child) = self.get_child(dir, name) {
+            return match self.node(child).kind {
+                NodeKind::File { size: recorded } if recorded == size => Ok(child),
+                NodeKind::File { size: recorded } => Err(FilesystemError::InconsistentSize {
+                    path: self.path(child),
+                    recorded,
+                    listed: size,
+                }),
+                NodeKind::Directory { .. } => Err(FilesystemError::IsADirectory(self.path(child))),
+            };
+        }
+        let child = self.push_child(dir, name, NodeKind::File { size })?;
+        // Keep the ancestors total sizes up to date.
+        let mut ancestor = Some(dir);
+        while let Some(id) = ancestor {
+            if let NodeKind::Directory { total_size, .. } = &mut self.nodes[id.0].kind {
+                *total_size += size;
+            }
+            ancestor = self.parent(id);
+        }
+        Ok(child)
+    }
+
+    fn push_child(
+        &mut self,
+        dir: NodeId,
+        name: &'a str,
+        kind: NodeKind<'a>,
+    ) -> Result<NodeId, FilesystemError> {
+        let child = NodeId(self.nodes.len());
+        match &mut self.nodes[dir.0].kind {
+            NodeKind::Directory {
+                children, by_name, ..
+            } => {
+                children.push(child);
+                by_name.insert(name, child);
+            }
+            NodeKind::File { .. } => return Err(FilesystemError::NotADirectory(self.path(dir))),
+        }
+        self.nodes.push(Node {
+            name,
+            parent: Some(dir),
+            kind,
+        });
+        Ok(child)
+    }
+
+    /// Depth first traversal starting from the root.
+    pub fn iter(&self) -> DepthFirstIteratorFS<'_, 'a> {
+        DepthFirstIteratorFS::new(self, self.root())
+    }
+}
+
+pub struct DepthFirstIteratorFS<'f, 'a> {
+    fs: &'f Filesystem<'a>,
+    waiting_stack: Vec<NodeId>,
+}
+
+impl<'f, 'a> DepthFirstIteratorFS<'f, 'a> {
+    pub fn new(fs: &'f Filesystem<'a>, start: NodeId) -> Self {
+        Self {
+            fs,
+            waiting_stack: vec![start],
+        }
+    }
+}
+
+impl<'f, 'a> Iterator for DepthFirstIteratorFS<'f, 'a> {
+    type Item = (NodeId, &'f Node<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.waiting_stack.pop()?;
+        let node = self.fs.node(id);
+        self.waiting_stack.extend(node.children().iter().rev());
+        Some((id, node))
+    }
+}
@@ -1,330 +1,40 @@
-use std::{cell::RefCell, fmt::Display, rc::Rc, str::Lines, sync::atomic::AtomicUsize};
-
-use itertools::Itertools;
-
-struct CLIParser<'a, 'b> {
-    input: &'a mut Lines<'b>,
-}
-
-impl<'a, 'b> CLIParser<'a, 'b> {
-    fn new(input: &'a mut Lines<'b>) -> Self {
-        Self { input }
-    }
-
-    fn parse(&mut self) -> Rc<RefCell<Filesystem>> {
-        use Filesystem::*;
-        let fs = Command::parse_commands(self.input).iter().fold(
-            Rc::new(RefCell::new(Directory {
-                name: Filesystem::ROOT_NAME,
-                children: Vec::new(),
-                parent: None,
-            })),
-            |pwd, command| command.deduce_fs(pwd),
-        );
-        let b = fs.borrow().root();
-        if let Some(root) = b {
-            root
-        } else {
-            fs
-        }
-    }
-}
-
-enum Command<'a> {
-    CD { path: &'a str },
-    LS { output: Vec<LsOutput<'a>> },
-}
-
-enum LsOutput<'a> {
-    Dir { name: &'a str },
-    File { size: usize, name: &'a str },
-}
-
-impl<'a> Command<'a> {
-    const COMMAND_LINE_START: char = '$';
-
-    fn is_command(input: &str) -> bool {
-        input.starts_with(Self::COMMAND_LINE_START)
-    }
-
-    fn parse_commands(lines: &mut Lines<'a>) -> Vec<Self> {
-        let mut v = Vec::new();
-        while let Some(command_line) = lines.next() {
-            let res = lines.take_while_ref(|line| !Self::is_command(line)).fold(
-                vec![command_line],
-                |mut acc, cur| {
-                    acc.push(cur);
-                    acc
-                },
-            );
-            v.push(res);
-        }
-
-        v.into_iter()
-            .map(|command_group| {
-                use Command::*;
-                let s = command_group[0].split_ascii_whitespace().collect_vec();
-                match s[1] {
-                    "cd" => CD { path: s[2] },
-                    "ls" => {
-                        let output = command_group[1..]
-                            .iter()
-                            .map(|o| {
-                                use LsOutput::*;
-                                let v = o.split_ascii_whitespace().collect_vec();
-                                match v[0] {
-                                    "dir" => Dir { name: v[1] },
-                                    _ => File {
-                                        size: v[0].parse::<usize>().expect("safe"),
-                                        name: v[1],
-                                    },
-                                }
-                            })
-                            .collect_vec();
-                        LS { output }
-                    }
-                    _ => panic!("impossible"),
-                }
-            })
-            .collect_vec()
-    }
-
-    fn deduce_fs(&self, pwd: Rc<RefCell<Filesystem<'a>>>) -> Rc<RefCell<Filesystem<'a>>> {
-        use Command::*;
-        match self {
-            CD { path } => match *path {
-                "/" => {
-                    let b = pwd.borrow().root();
-                    if let Some(root) = b {
-                        root
-                    } else {
-                        pwd
-                    }
-                }
-                ".." => {
-                    let b = pwd.borrow().parent();
-                    if let Some(parent) = b {
-                        parent
-                    } else {
-                        pwd
-                    }
-                }
-                dir => {
-                    // Move into target or create it in our FS.
-                    if let Some(child) = pwd.borrow().get_child(dir) {
-                        child
-                    } else {
-                        let child = Rc::new(RefCell::new(Filesystem::Directory {
-                            name: dir,
-                            parent: Some(Rc::clone(&pwd)),
-                            children: Vec::new(),
-                        }));
-                        pwd.borrow_mut().add_child(Rc::clone(&child)).expect("safe");
-                        child
-                    }
-                }
-            },
-            LS { output } => {
-                output.iter().for_each(|out| {
-                    use LsOutput::*;
-                    match out {
-                        Dir { name } => {
-                            if !pwd.borrow().has(name) {
-                                let child = Rc::new(RefCell::new(Filesystem::Directory {
-                                    name,
-                                    parent: Some(Rc::clone(&pwd)),
-                                    children: Vec::new(),
-                                }));
-                                pwd.borrow_mut().add_child(Rc::clone(&child)).expect("safe");
-                            }
-                        }
-                        File { size, name } => {
-                            if !pwd.borrow().has(name) {
-                                let child = Rc::new(RefCell::new(Filesystem::File {
-                                    name,
-                                    size: *size,
-                                    parent: Rc::clone(&pwd),
-                                }));
-                                pwd.borrow_mut().add_child(Rc::clone(&child)).expect("safe");
-                            }
-                        }
-                    };
-                });
-                pwd
-            }
-        }
-    }
-}
-
-enum Filesystem<'a> {
-    Directory {
-        name: &'a str,
-        parent: Option<Rc<RefCell<Filesystem<'a>>>>, // Should only be Directory...
-        children: Vec<Rc<RefCell<Filesystem<'a>>>>,
-    },
-    File {
-        name: &'a str,
-        size: usize,
-        parent: Rc<RefCell<Filesystem<'a>>>, // Should only be Directory...
-    },
-}
-
-impl<'a> Filesystem<'a> {
-    const ROOT_NAME: &'static str = "/";
-
-    fn is_dir(&self) -> bool {
-        use Filesystem::*;
-        match self {
-            Directory { .. } => true,
-            File { .. } => false,
-        }
-    }
-
-    fn name(&self) -> &str {
-        use Filesystem::*;
-        match self {
-            Directory { name, .. } => name,
-            File { name, .. } => name,
-        }
-    }
-
-    fn parent(&self) -> Option<Rc<RefCell<Filesystem<'a>>>> {
-        use Filesystem::*;
-        match self {
-            File { parent, .. } => Some(Rc::clone(parent)),
-            Directory { parent, .. } => parent.clone(),
-        }
-    }
-
-    fn root(&self) -> Option<Rc<RefCell<Filesystem<'a>>>> {
-        use Filesystem::*;
-        match self {
-            Directory { parent: None, .. } => None,
-            File { parent, .. }
-            | Directory {
-                parent: Some(parent),
-                ..
-            } => {
-                if parent.borrow().name() == Self::ROOT_NAME {
-                    Some(Rc::clone(parent))
-                } else {
-                    parent.borrow().root()
-                }
-            }
-        }
-    }
-
-    fn has(&self, p: &str) -> bool {
-        use Filesystem::*;
-        match self {
-            Directory { children, .. } => children
-                .iter()
-                .any(|child| child.as_ref().borrow().name() == p),
-            File { .. } => false,
-        }
-    }
-
-    fn get_child(&self, p: &str) -> Option<Rc<RefCell<Filesystem<'a>>>> {
-        use Filesystem::*;
-        match self {
-            Directory { children, .. } => children
-                .iter()
-                .find(|child| child.as_ref().borrow().name() == p)
-                .cloned(),
-            File { .. } => None,
-        }
-    }
-
-    fn size(&self) -> usize {
-        use Filesystem::*;
-        match self {
-            File { size, .. } => *size,
-            Directory { children, .. } => children.iter().map(|child| child.borrow().size()).sum(),
-        }
-    }
-
-    fn add_child(&mut self, child: Rc<RefCell<Filesystem<'a>>>) -> Result<(), String> {
-        use Filesystem::*;
-        match self {
-            Directory { children, .. } => {
-                children.push(Rc::clone(&child));
-                Ok(())
-            }
-            File { .. } => Err("Not a directory".to_string()),
-        }
-    }
-}
-
-struct DepthFirstIteratorFS<'a> {
-    waiting_stack: Vec<Rc<RefCell<Filesystem<'a>>>>,
-}
-
-impl<'a> From<Rc<RefCell<Filesystem<'a>>>> for DepthFirstIteratorFS<'a> {
-    fn from(value: Rc<RefCell<Filesystem<'a>>>) -> Self {
-        DepthFirstIteratorFS {
-            waiting_stack: vec![value],
-        }
-    }
-}
-
-impl<'a> Iterator for DepthFirstIteratorFS<'a> {
-    type Item = Rc<RefCell<Filesystem<'a>>>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let node = self.waiting_stack.pop()?;
-        use Filesystem::*;
-        match &*node.borrow() {
-            File { .. } => {}
-            Directory { children, .. } => children.iter().rev().for_each(|child| {
-                self.waiting_stack.push(Rc::clone(child));
-            }),
-        };
-        Some(node)
-    }
-}
-
-static DISPLAY_COUNTER: AtomicUsize = AtomicUsize::new(0);
-
-impl<'a> Display for Filesystem<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        use Filesystem::*;
-        match self {
-            File { name, size, .. } => write!(f, "- {name} (file, size={size})"),
-            Directory { name, children, .. } => write!(f, "- {name} (dir)").and_then(|_| {
-                DISPLAY_COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-                let c = children.iter().try_for_each(|child| {
-                    let alignment =
-                        " ".repeat(DISPLAY_COUNTER.load(std::sync::atomic::Ordering::Relaxed) * 4);
-                    write!(f, "\n{alignment}{}", child.borrow())
-                });
-                DISPLAY_COUNTER.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
-                c
-            }),
-        }
-    }
+use std::str::Lines;
+
+mod disk;
+pub use disk::transcript_from_dir;
+pub mod export;
+pub mod filesystem;
+pub use filesystem::*;
+pub mod planner;
+pub use planner::{DeletionPlan, DeletionPlanner, DeletionStrategy, PlanError};
+pub mod query;
+pub use query::{Glob, GlobError};
+pub mod report;
+pub use report::{ReportOptions, SortBy};
+mod transcript;
+use transcript::CLIParser;
+pub use transcript::TranscriptError;
+
+/// Builds the filesystem described by a shell transcript.
+///
+/// Unknown commands are skipped with a warning.
+pub fn parse_filesystem<'a>(input: &mut Lines<'a>) -> Result<Filesystem<'a>, TranscriptError> {
+    CLIParser::new(input).parse()
 }
 
 pub fn solve_part1(input: &mut Lines) -> usize {
-    let mut parser = CLIParser::new(input);
-    let fs = parser.parse();
-    //println!("{}", fs.borrow());
-    DepthFirstIteratorFS::from(fs)
-        .filter(|node| node.borrow().is_dir())
-        .map(|n| n.borrow().size())
+    let fs = parse_filesystem(input).expect("safe");
+    //println!("{fs}");
+    fs.iter()
+        .filter(|(_, node)| node.is_dir())
+        .map(|(_, node)| node.size())
         .filter(|size| *size <= 100_000)
         .sum()
 }
 
 pub fn solve_part2(input: &mut Lines) -> usize {
-    let mut parser = CLIParser::new(input);
-    let fs = parser.parse();
-    let space_to_free = 30000000 - (70000000 - fs.borrow().size());
-    DepthFirstIteratorFS::from(fs)
-        .filter(|node| node.borrow().is_dir())
-        .map(|n| n.borrow().size())
-        .filter(|size| *size >= space_to_free)
-        .min()
-        .expect("safe")
+    let fs = parse_filesystem(input).expect("safe");
+    DeletionPlanner::default().plan(&fs).expect("safe").freed
 }
 
 #[cfg(test)]
@@ -364,4 +74,378 @@ $ ls
     fn test_part2() {
         assert_eq!(solve_part2(&mut INPUT.lines()), 24933642);
     }
+
+    #[test]
+    fn test_filesystem() {
+        let fs = parse_filesystem(&mut INPUT.lines()).unwrap();
+        let root = fs.root();
+        let a = fs.get_child(root, "a").unwrap();
+        let e = fs.get_child(a, "e").unwrap();
+        let i = fs.get_child(e, "i").unwrap();
+        assert_eq!(fs.parent(i), Some(e));
+        assert_eq!(fs.parent(root), None);
+        assert_eq!(fs.size(e), 584);
+        assert_eq!(fs.size(a), 94853);
+        assert_eq!(fs.size(root), 48381165);
+        assert_eq!(fs.get_child(i, "x"), None);
+        assert_eq!(
+            fs.to_string(),
+            "- / (dir)
+    - a (dir)
+        - e (dir)
+            - i (file, size=584)
+        - f (file, size=29116)
+        - g (file, size=2557)
+        - h.lst (file, size=62596)
+    - b.txt (file, size=14848514)
+    - c.dat (file, size=8504156)
+    - d (dir)
+        - j (file, size=4060174)
+        - d.log (file, size=8033020)
+        - d.ext (file, size=5626152)
+        - k (file, size=7214296)"
+        );
+    }
+
+    #[test]
+    fn test_reports() {
+        let fs = parse_filesystem(&mut INPUT.lines()).unwrap();
+        let options = ReportOptions {
+            max_depth: Some(1),
+            sort_by: SortBy::Size,
+            human_readable: true,
+            dir_sizes: true,
+        };
+        assert_eq!(
+            fs.tree(options).to_string(),
+            "- / (dir, size=47M)
+    - d (dir, size=24M)
+    - b.txt (file, size=15M)
+    - c.dat (file, size=8.2M)
+    - a (dir, size=93K)"
+        );
+        assert_eq!(
+            fs.du(ReportOptions::default()).to_string(),
+            "584\t/a/e\n94853\t/a\n24933642\t/d\n48381165\t/"
+        );
+        let options = ReportOptions {
+            sort_by: SortBy::Name,
+            ..Default::default()
+        };
+        assert_eq!(
+            fs.du(options).to_string(),
+            "48381165\t/\n94853\t/a\n584\t/a/e\n24933642\t/d"
+        );
+        let options = ReportOptions {
+            max_depth: Some(0),
+            human_readable: true,
+            ..Default::default()
+        };
+        assert_eq!(fs.du(options).to_string(), "47M\t/");
+    }
+
+    #[test]
+    fn test_transcript() {
+        let input = "$ cd /a/b/../c
+$ ls
+10 f
+$ pwd
+/a/c
+$ cd ../b/./
+$ ls
+dir d
+20 g
+$ cd d
+$ cd /
+$ cd a
+$ ls
+dir b
+dir c
+$ cd c
+$ ls
+10 f";
+        let fs = parse_filesystem(&mut input.lines()).unwrap();
+        let a = fs.get_child(fs.root(), "a").unwrap();
+        let c = fs.get_child(a, "c").unwrap();
+        let d = fs.get_child(fs.get_child(a, "b").unwrap(), "d").unwrap();
+        assert_eq!(fs.path(d), "/a/b/d");
+        assert_eq!(fs.size(c), 10);
+        assert_eq!(fs.size(a), 30);
+        assert_eq!(fs.iter().count(), 7);
+    }
+
+    #[test]
+    fn test_transcript_errors() {
+        let input = "$ cd /
+$ ls
+10 f
+$ ls
+11 f";
+        assert_eq!(
+            parse_filesystem(&mut input.lines()).unwrap_err(),
+            TranscriptError::Filesystem {
+                line: 4,
+                source: FilesystemError::InconsistentSize {
+                    path: "/f".to_string(),
+                    recorded: 10,
+                    listed: 11
+                }
+            }
+        );
+        let input = "$ cd /
+$ ls
+10 f
+$ cd f/g";
+        assert_eq!(
+            parse_filesystem(&mut input.lines()).unwrap_err(),
+            TranscriptError::Filesystem {
+                line: 4,
+                source: FilesystemError::NotADirectory("/f".to_string())
+            }
+        );
+        let input = "$ ls
+ten f";
+        assert_eq!(
+            parse_filesystem(&mut input.lines()).unwrap_err(),
+            TranscriptError::InvalidLsOutput {
+                line: 2,
+                output: "ten f".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_materialize_round_trip() {
+        let fs = parse_filesystem(&mut INPUT.lines()).unwrap();
+        let target = tempfile::tempdir().unwrap();
+        fs.materialize(target.path()).unwrap();
+        let metadata = std::fs::metadata(target.path().join("d").join("d.log")).unwrap();
+        assert_eq!(metadata.len(), 8033020);
+
+        let transcript = transcript_from_dir(target.path()).unwrap();
+        assert!(transcript.starts_with("$ cd /\n$ ls\ndir a\n14848514 b.txt\n"));
+        let imported = parse_filesystem(&mut transcript.lines()).unwrap();
+        let listing = |fs: &Filesystem| {
+            let mut v = fs
+                .iter()
+                .map(|(id, node)| (fs.path(id), node.size()))
+                .collect::<Vec<_>>();
+            v.sort();
+            v
+        };
+        assert_eq!(listing(&imported), listing(&fs));
+    }
+
+    #[test]
+    fn test_transcript_from_dir_names() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("a b")).unwrap();
+        std::fs::write(dir.path().join("a b").join("c  d.txt"), "12345").unwrap();
+        let transcript = transcript_from_dir(dir.path()).unwrap();
+        let fs = parse_filesystem(&mut transcript.lines()).unwrap();
+        let paths = fs.iter().map(|(id, _)| fs.path(id)).collect::<Vec<_>>();
+        assert!(
+            paths.iter().any(|path| path == "/a b/c  d.txt"),
+            "{paths:?}"
+        );
+        assert_eq!(fs.node(fs.root()).size(), 5);
+
+        for name in ["new\nline", " leading", "trailing ", "carriage\r"] {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::write(dir.path().join(name), "").unwrap();
+            let err = transcript_from_dir(dir.path()).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData, "{name:?}");
+        }
+    }
+
+    #[test]
+    fn test_materialize_rejects_escaping_names() {
+        let input = "$ cd /
+$ ls
+dir ..";
+        let fs = parse_filesystem(&mut input.lines()).unwrap();
+        let target = tempfile::tempdir().unwrap();
+        let err = fs.materialize(target.path()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_query() {
+        let fs = parse_filesystem(&mut INPUT.lines()).unwrap();
+        let paths = fs.walk().map(|entry| entry.path).collect::<Vec<_>>();
+        assert_eq!(paths[..5], ["/", "/a", "/a/e", "/a/e/i", "/a/f"]);
+        assert_eq!(paths.len(), fs.iter().count());
+
+        let i = fs.lookup("/a/e/i").unwrap();
+        assert_eq!(fs.node(i).size(), 584);
+        assert_eq!(fs.lookup("/a/e/../../d/./k"), fs.lookup("/d/k"));
+        assert_eq!(fs.lookup("/"), Some(fs.root()));
+        assert_eq!(fs.lookup("/x"), None);
+        assert_eq!(fs.lookup("a"), None);
+
+        let query = |pattern| {
+            fs.query()
+                .glob(Glob::new(pattern).unwrap())
+                .iter()
+                .map(|entry| entry.path)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(query("/a/**/*.lst"), ["/a/h.lst"]);
+        assert_eq!(query("/**/d*"), ["/d", "/d/d.log", "/d/d.ext"]);
+        assert_eq!(query("/*/?"), ["/a/e", "/a/f", "/a/g", "/d/j", "/d/k"]);
+        assert_eq!(query("/**/e/**"), ["/a/e", "/a/e/i"]);
+        assert_eq!(query("/").len(), 1);
+        assert_eq!(
+            Glob::new("a/*"),
+            Err(GlobError::NotAbsolute("a/*".to_string()))
+        );
+
+        let small_dirs = fs
+            .query()
+            .dirs_only()
+            .size(..=100_000)
+            .iter()
+            .map(|entry| entry.path)
+            .collect::<Vec<_>>();
+        assert_eq!(small_dirs, ["/a", "/a/e"]);
+        let big_files = fs.query().files_only().size(8_000_000..).iter().count();
+        assert_eq!(big_files, 3);
+    }
+
+    #[test]
+    fn test_planner() {
+        let fs = parse_filesystem(&mut INPUT.lines()).unwrap();
+        let mut planner = DeletionPlanner::default();
+        let plan = planner.plan(&fs).unwrap();
+        assert_eq!(plan.paths, ["/d"]);
+        assert_eq!(plan.freed, 24933642);
+        planner.strategy = DeletionStrategy::Entries;
+        let plan = planner.plan(&fs).unwrap();
+        assert_eq!(plan.paths, ["/c.dat"]);
+        assert_eq!(plan.freed, 8504156);
+        planner.required_free_space = 20_000_000;
+        assert!(planner.plan(&fs).unwrap().paths.is_empty());
+        planner.required_free_space = 80_000_000;
+        assert!(planner.plan(&fs).is_err());
+        planner.required_free_space = usize::MAX;
+        assert_eq!(
+            planner.plan(&fs),
+            Err(PlanError::NotEnoughSpace {
+                to_free: usize::MAX - 70_000_000 + 48381165,
+                used: 48381165
+            })
+        );
+        planner.disk_size = usize::MAX;
+        assert_eq!(planner.plan(&fs).unwrap().paths, ["/"]);
+        // A disk fuller than its size.
+        planner.disk_size = 10_000_000;
+        assert!(planner.plan(&fs).is_err());
+
+        let input = "$ cd /
+$ ls
+dir a
+dir b
+dir c
+$ cd a
+$ ls
+100 x
+$ cd ../b
+$ ls
+200 y
+dir n
+$ cd n
+$ ls
+50 z
+$ cd /c
+$ ls
+400 w";
+        let fs = parse_filesystem(&mut input.lines()).unwrap();
+        // 750 bytes are used, so 250 are already free.
+        let plan = |to_free: usize, strategy| {
+            let planner = DeletionPlanner {
+                disk_size: 1000,
+                required_free_space: to_free + 250,
+                strategy,
+            };
+            planner.plan(&fs).unwrap()
+        };
+        use DeletionStrategy::*;
+        assert_eq!(plan(350, SmallestDirectory).paths, ["/c"]);
+        assert_eq!(plan(350, Directories).paths, ["/a", "/b"]);
+        assert_eq!(plan(150, Directories).paths, ["/a", "/b/n"]);
+        assert_eq!(plan(300, Directories).freed, 350);
+        assert_eq!(plan(300, Entries).paths, ["/a", "/b/y"]);
+        assert_eq!(plan(750, Entries).paths, ["/"]);
+
+        // Terabytes are counted in blocks rather than one bit per byte.
+        let input = "$ cd /
+$ ls
+dir a
+dir b
+dir c
+$ cd a
+$ ls
+3000000000000 x
+$ cd ../b
+$ ls
+2000000000001 y
+$ cd ../c
+$ ls
+5500000000000 z";
+        let fs = parse_filesystem(&mut input.lines()).unwrap();
+        let planner = DeletionPlanner {
+            disk_size: 11_000_000_000_000,
+            required_free_space: 5_000_000_000_000,
+            strategy: Directories,
+        };
+        let plan = planner.plan(&fs).unwrap();
+        assert_eq!(plan.paths, ["/a", "/b"]);
+        assert!(plan.freed >= 4_500_000_000_001);
+    }
+
+    #[test]
+    fn test_export() {
+        let input = "$ cd /
+$ ls
+dir a
+10 \"b\"
+$ cd a
+$ ls
+5 c";
+        let fs = parse_filesystem(&mut input.lines()).unwrap();
+        assert_eq!(
+            fs.to_json(),
+            serde_json::json!({
+                "name": "/",
+                "kind": "dir",
+                "size": null,
+                "total_size": 15,
+                "children": [
+                    {
+                        "name": "a",
+                        "kind": "dir",
+                        "size": null,
+                        "total_size": 5,
+                        "children": [
+                            { "name": "c", "kind": "file", "size": 5, "total_size": 5 },
+                        ],
+                    },
+                    { "name": "\"b\"", "kind": "file", "size": 10, "total_size": 10 },
+                ],
+            })
+        );
+        assert_eq!(
+            fs.dot().to_string(),
+            r#"digraph filesystem {
+    node [fontname="monospace"];
+    n0 [shape=folder, label="/\n15"];
+    n1 [shape=folder, label="a\n5"];
+    n0 -> n1;
+    n3 [shape=note, label="c\n5"];
+    n1 -> n3;
+    n2 [shape=note, label="\"b\"\n10"];
+    n0 -> n2;
+}"#
+        );
+    }
 }
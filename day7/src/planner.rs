@@ -0,0 +1,272 @@
+use thiserror::Error;
+
+use crate::{Filesystem, NodeId};
+
+/// Combination strategies track which sums of sizes can be freed, up to the
+/// size of the smallest single candidate freeing enough space. Past
+/// [`MAX_TRACKED_SUMS`] sums, sizes are rounded down to blocks so that memory
+/// stays bounded, and the plan may then free a bit more than the best one.
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum DeletionStrategy {
+    /// Delete the smallest directory freeing enough space.
+    #[default]
+    SmallestDirectory,
+    /// Delete the combination of non-nested directories freeing the fewest bytes.
+    Directories,
+    /// Same as `Directories`, files being candidates too.
+    Entries,
+}
+
+/// Most sums tracked when looking for the best combination of deletions.
+pub const MAX_TRACKED_SUMS: usize = 1 << 20;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct DeletionPlanner {
+    pub disk_size: usize,
+    pub required_free_space: usize,
+    pub strategy: DeletionStrategy,
+}
+
+impl Default for DeletionPlanner {
+    fn default() -> Self {
+        Self {
+            disk_size: 70_000_000,
+            required_free_space: 30_000_000,
+            strategy: DeletionStrategy::default(),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct DeletionPlan {
+    /// Absolute paths of the nodes to delete, in depth first order.
+    pub paths: Vec<String>,
+    pub freed: usize,
+}
+
+#[derive(Error, PartialEq, Eq, Debug)]
+pub enum PlanError {
+    #[error("{to_free} bytes must be freed but only {used} are used")]
+    NotEnoughSpace { to_free: usize, used: usize },
+}
+
+impl DeletionPlanner {
+    pub fn plan(&self, fs: &Filesystem) -> Result<DeletionPlan, PlanError> {
+        let used = fs.size(fs.root());
+        // Only an overfull disk needs more than the required free space, and
+        // more than fits in a usize is more than can be freed anyway.
+        let to_free = match self.disk_size.checked_sub(used) {
+            Some(free) => self.required_free_space.saturating_sub(free),
+            None => self
+                .required_free_space
+                .saturating_add(used - self.disk_size),
+        };
+        if to_free > used {
+            return Err(PlanError::NotEnoughSpace { to_free, used });
+        }
+        if to_free == 0 {
+            return Ok(DeletionPlan {
+                paths: Vec::new(),
+                freed: 0,
+            });
+        }
+        let is_candidate = |id: NodeId| match self.strategy {
+            DeletionStrategy::SmallestDirectory | DeletionStrategy::Directories => {
+                fs.node(id).is_dir()
+            }
+            DeletionStrategy::Entries => true,
+        };
+        // The root frees everything so there always is a smallest candidate.
+        let (smallest, smallest_size) = fs
+            .iter()
+            .filter(|&(id, node)| is_candidate(id) && node.size() >= to_free)
+            .map(|(id, node)| (id, node.size()))
+            .min_by_key(|(_, size)| *size)
+            .expect("safe");
+        let ids = match self.strategy {
+            DeletionStrategy::SmallestDirectory => vec![smallest],
+            DeletionStrategy::Directories | DeletionStrategy::Entries => {
+                best_combination(fs, to_free, smallest_size, is_candidate)
+                    .filter(|ids| ids.iter().map(|&id| fs.size(id)).sum::<usize>() < smallest_size)
+                    .unwrap_or(vec![smallest])
+            }
+        };
+        Ok(DeletionPlan {
+            freed: ids.iter().map(|&id| fs.size(id)).sum(),
+            paths: ids.into_iter().map(|id| fs.path(id)).collect(),
+        })
+    }
+}
+
+// Finds the non-nested candidates whose sizes sum to the smallest amount
+// above `to_free`, knowing `upper_bound` is reachable. Sizes are counted in
+// blocks so that at most MAX_TRACKED_SUMS sums are tracked, rounding them
+// down so that any combination found frees enough: None if none is left.
+//
+// Candidates are laid out in depth first order so that a node's subtree is a
+// contiguous range ending at `subtree_end`. The sums reachable from position
+// i onwards are then the ones reachable from i + 1 (skipping the node),
+// plus the ones reachable past its subtree shifted by its size (deleting it).
+fn best_combination(
+    fs: &Filesystem,
+    to_free: usize,
+    upper_bound: usize,
+    is_candidate: impl Fn(NodeId) -> bool,
+) -> Option<Vec<NodeId>> {
+    let mut candidates = Vec::new();
+    let mut subtree_end = Vec::new();
+    collect_candidates(
+        fs,
+        fs.root(),
+        &is_candidate,
+        &mut candidates,
+        &mut subtree_end,
+    );
+
+    let block = upper_bound / MAX_TRACKED_SUMS + 1;
+    let (to_free, upper_bound) = (to_free.div_ceil(block), upper_bound / block);
+    if to_free > upper_bound {
+        return None;
+    }
+    let n = candidates.len();
+    let sizes = candidates
+        .iter()
+        .map(|&id| fs.size(id) / block)
+        .collect::<Vec<_>>();
+    // Sums past the upper bound are never better, so they are not tracked.
+    // added_at[sum] is the last position from which the sum is reachable,
+    // reachable sums only growing as the position decreases.
+    const UNREACHABLE: u32 = u32::MAX;
+    let mut added_at = vec![UNREACHABLE; upper_bound + 1];
+    added_at[0] = n as u32;
+    let mut next = BitSet::new(upper_bound + 1);
+    next.insert(0);
+    // Reachable sets are only saved where a subtree ends, until the first
+    // node of that subtree is processed.
+    let mut last_needed = vec![None; n + 1];
+    for (i, &end) in subtree_end.iter().enumerate() {
+        last_needed[end].get_or_insert(i);
+    }
+    let mut saved = vec![None; n + 1];
+    if last_needed[n].is_some() {
+        saved[n] = Some(next.clone());
+    }
+    for i in (0..n).rev() {
+        let end = subtree_end[i];
+        let past_subtree = if end == i + 1 {
+            &next
+        } else {
+            saved[end].as_ref().expect("saved before its subtree")
+        };
+        let mut reachable = next.clone();
+        reachable.union_shifted(past_subtree, sizes[i]);
+        if last_needed[end] == Some(i) {
+            saved[end] = None;
+        }
+        for sum in reachable.difference(&next) {
+            added_at[sum] = i as u32;
+        }
+        if last_needed[i].is_some() {
+            saved[i] = Some(reachable.clone());
+        }
+        next = reachable;
+    }
+
+    let mut sum = (to_free..=upper_bound).find(|&sum| next.contains(sum))?;
+    let mut chosen = Vec::new();
+    let mut i = 0;
+    while sum > 0 {
+        // Prefer deleting a whole directory over some of its entries.
+        // Deleting nothing is never needed.
+        let rest = sum
+            .checked_sub(sizes[i])
+            .filter(|_| sizes[i] > 0)
+            .map(|rest| added_at[rest]);
+        if rest.is_some_and(|at| at != UNREACHABLE && at as usize >= subtree_end[i]) {
+            chosen.push(candidates[i]);
+            sum -= sizes[i];
+            i = subtree_end[i];
+        } else {
+            i += 1;
+        }
+    }
+    Some(chosen)
+}
+
+fn collect_candidates(
+    fs: &Filesystem,
+    id: NodeId,
+    is_candidate: &impl Fn(NodeId) -> bool,
+    candidates: &mut Vec<NodeId>,
+    subtree_end: &mut Vec<usize>,
+) {
+    if !is_candidate(id) {
+        return;
+    }
+    let pos = candidates.len();
+    candidates.push(id);
+    subtree_end.push(pos + 1);
+    for &child in fs.node(id).children() {
+        collect_candidates(fs, child, is_candidate, candidates, subtree_end);
+    }
+    subtree_end[pos] = candidates.len();
+}
+
+#[derive(Clone)]
+struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitSet {
+    fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    fn contains(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    /// Adds every element of `other` increased by `shift`, dropping the ones
+    /// past the set length.
+    fn union_shifted(&mut self, other: &Self, shift: usize) {
+        let (word_shift, bit_shift) = (shift / 64, shift % 64);
+        for i in word_shift..self.words.len() {
+            let src = i - word_shift;
+            let mut word = other.words[src] << bit_shift;
+            if bit_shift > 0 && src > 0 {
+                word |= other.words[src - 1] >> (64 - bit_shift);
+            }
+            self.words[i] |= word;
+        }
+        // Clear the bits past the end.
+        if !self.len.is_multiple_of(64) {
+            *self.words.last_mut().expect("safe") &= (1 << (self.len % 64)) - 1;
+        }
+    }
+
+    /// Elements of `self` missing from `other`.
+    fn difference<'s>(&'s self, other: &'s Self) -> impl Iterator<Item = usize> + 's {
+        self.words
+            .iter()
+            .zip(&other.words)
+            .enumerate()
+            .flat_map(|(i, (a, b))| {
+                let mut word = a & !b;
+                std::iter::from_fn(move || {
+                    (word != 0).then(|| {
+                        let bit = word.trailing_zeros() as usize;
+                        word &= word - 1;
+                        i * 64 + bit
+                    })
+                })
+            })
+    }
+}
@@ -0,0 +1,196 @@
+use std::ops::{Bound, RangeBounds};
+
+use thiserror::Error;
+
+use crate::{Filesystem, Node, NodeId};
+
+/// A node along with its absolute path.
+#[derive(Debug)]
+pub struct PathEntry<'f, 'a> {
+    pub path: String,
+    pub id: NodeId,
+    pub node: &'f Node<'a>,
+}
+
+/// Depth first traversal yielding the full path of every node.
+pub struct PathIterator<'f, 'a> {
+    fs: &'f Filesystem<'a>,
+    waiting_stack: Vec<(NodeId, String)>,
+}
+
+impl<'f, 'a> Iterator for PathIterator<'f, 'a> {
+    type Item = PathEntry<'f, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (id, path) = self.waiting_stack.pop()?;
+        let node = self.fs.node(id);
+        let separator = if path.ends_with(Filesystem::ROOT_NAME) {
+            ""
+        } else {
+            Filesystem::ROOT_NAME
+        };
+        self.waiting_stack
+            .extend(node.children().iter().rev().map(|&child| {
+                let name = self.fs.node(child).name();
+                (child, format!("{path}{separator}{name}"))
+            }));
+        Some(PathEntry { path, id, node })
+    }
+}
+
+#[derive(Error, PartialEq, Eq, Debug)]
+pub enum GlobError {
+    #[error("{0:?} is not an absolute pattern")]
+    NotAbsolute(String),
+}
+
+/// An absolute path pattern where `*` and `?` match within a name and a
+/// `**` segment matches any number of directories, e.g. `/a/**/*.lst`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Glob {
+    segments: Vec<String>,
+}
+
+impl Glob {
+    const ANY_DIRS: &'static str = "**";
+
+    pub fn new(pattern: &str) -> Result<Self, GlobError> {
+        let Some(relative) = pattern.strip_prefix(Filesystem::ROOT_NAME) else {
+            return Err(GlobError::NotAbsolute(pattern.to_string()));
+        };
+        let segments = relative
+            .split(Filesystem::ROOT_NAME)
+            .filter(|segment| !segment.is_empty())
+            .map(str::to_string)
+            .collect();
+        Ok(Self { segments })
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        let names = path
+            .split(Filesystem::ROOT_NAME)
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+        // matches[j] tells if the segments seen so far match names[..j].
+        let mut matches = vec![false; names.len() + 1];
+        matches[0] = true;
+        for segment in &self.segments {
+            let mut next = vec![false; names.len() + 1];
+            for j in 0..=names.len() {
+                next[j] = if segment == Self::ANY_DIRS {
+                    matches[j] || (j > 0 && next[j - 1])
+                } else {
+                    j > 0 && matches[j - 1] && wildcard_match(segment, names[j - 1])
+                };
+            }
+            matches = next;
+        }
+        matches[names.len()]
+    }
+}
+
+// Matches a single name against a pattern made of `*`, `?` and literals.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // Last `*` seen and the name position it currently stops at.
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more char.
+                Some((star, star_n)) => {
+                    backtrack = Some((star, star_n + 1));
+                    p = star + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Filters over the nodes of a filesystem, see [`Filesystem::query`].
+pub struct Query<'f, 'a> {
+    fs: &'f Filesystem<'a>,
+    glob: Option<Glob>,
+    size: (Bound<usize>, Bound<usize>),
+    is_dir: Option<bool>,
+}
+
+impl<'f, 'a> Query<'f, 'a> {
+    /// Only keep nodes whose path matches `glob`.
+    pub fn glob(mut self, glob: Glob) -> Self {
+        self.glob = Some(glob);
+        self
+    }
+
+    /// Only keep nodes whose size, or total size for directories, is in range.
+    pub fn size(mut self, range: impl RangeBounds<usize>) -> Self {
+        self.size = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+
+    pub fn dirs_only(mut self) -> Self {
+        self.is_dir = Some(true);
+        self
+    }
+
+    pub fn files_only(mut self) -> Self {
+        self.is_dir = Some(false);
+        self
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = PathEntry<'f, 'a>> + '_ {
+        self.fs.walk().filter(|entry| {
+            self.is_dir
+                .is_none_or(|is_dir| entry.node.is_dir() == is_dir)
+                && self.size.contains(&entry.node.size())
+                && self
+                    .glob
+                    .as_ref()
+                    .is_none_or(|glob| glob.is_match(&entry.path))
+        })
+    }
+}
+
+impl<'a> Filesystem<'a> {
+    /// Depth first traversal from the root, with the path of each node.
+    pub fn walk(&self) -> PathIterator<'_, 'a> {
+        PathIterator {
+            fs: self,
+            waiting_stack: vec![(self.root(), Self::ROOT_NAME.to_string())],
+        }
+    }
+
+    /// Finds a node from its absolute path, `.` and `..` being resolved.
+    pub fn lookup(&self, path: &str) -> Option<NodeId> {
+        path.strip_prefix(Self::ROOT_NAME)?
+            .split(Self::ROOT_NAME)
+            .try_fold(self.root(), |dir, segment| match segment {
+                "" | "." => Some(dir),
+                ".." => Some(self.parent(dir).unwrap_or(dir)),
+                name => self.get_child(dir, name),
+            })
+    }
+
+    /// Starts a query matching every node.
+    pub fn query(&self) -> Query<'_, 'a> {
+        Query {
+            fs: self,
+            glob: None,
+            size: (Bound::Unbounded, Bound::Unbounded),
+            is_dir: None,
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt::{self, Display};
+
+use crate::{Filesystem, NodeId, NodeKind};
+
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum SortBy {
+    /// Keep the order in which the transcript listed the entries.
+    #[default]
+    Listing,
+    Name,
+    /// Largest first.
+    Size,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub struct ReportOptions {
+    /// Deepest level shown, the root being at depth 0.
+    pub max_depth: Option<usize>,
+    pub sort_by: SortBy,
+    /// Print sizes like `du -h` does, e.g. `4.0K` or `46M`.
+    pub human_readable: bool,
+    /// Print the total size of directories in the tree, not only of files.
+    pub dir_sizes: bool,
+}
+
+impl ReportOptions {
+    fn shows_depth(&self, depth: usize) -> bool {
+        self.max_depth.is_none_or(|max_depth| depth <= max_depth)
+    }
+
+    fn size(&self, size: usize) -> Size {
+        Size {
+            size,
+            human_readable: self.human_readable,
+        }
+    }
+
+    fn sorted(&self, fs: &Filesystem, ids: &[NodeId]) -> Vec<NodeId> {
+        let mut ids = ids.to_vec();
+        match self.sort_by {
+            SortBy::Listing => {}
+            SortBy::Name => ids.sort_by_key(|&id| fs.node(id).name()),
+            SortBy::Size => ids.sort_by(|&a, &b| {
+                let (a, b) = (fs.node(a), fs.node(b));
+                b.size().cmp(&a.size()).then(a.name().cmp(b.name()))
+            }),
+        }
+        ids
+    }
+}
+
+struct Size {
+    size: usize,
+    human_readable: bool,
+}
+
+impl Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [char; 6] = ['K', 'M', 'G', 'T', 'P', 'E'];
+
+        if !self.human_readable || self.size < 1024 {
+            return write!(f, "{}", self.size);
+        }
+        let mut value = self.size as f64 / 1024.0;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        // Like du, round up and only keep a decimal for small values.
+        if value < 10.0 && (value * 10.0).ceil() < 100.0 {
+            write!(f, "{:.1}{}", (value * 10.0).ceil() / 10.0, UNITS[unit])
+        } else {
+            write!(f, "{}{}", value.ceil(), UNITS[unit])
+        }
+    }
+}
+
+/// Indented tree of the filesystem, see [`Filesystem::tree`].
+pub struct TreeReport<'f, 'a> {
+    fs: &'f Filesystem<'a>,
+    options: ReportOptions,
+}
+
+impl<'f, 'a> TreeReport<'f, 'a> {
+    fn fmt_node(&self, f: &mut fmt::Formatter<'_>, id: NodeId, depth: usize) -> fmt::Result {
+        let node = self.fs.node(id);
+        let alignment = " ".repeat(depth * 4);
+        let size = self.options.size(node.size());
+        match node.kind() {
+            NodeKind::File { .. } => write!(f, "{alignment}- {} (file, size={size})", node.name()),
+            NodeKind::Directory { children, .. } => {
+                if self.options.dir_sizes {
+                    write!(f, "{alignment}- {} (dir, size={size})", node.name())?;
+                } else {
+                    write!(f, "{alignment}- {} (dir)", node.name())?;
+                }
+                if !self.options.shows_depth(depth + 1) {
+                    return Ok(());
+                }
+                self.options
+                    .sorted(self.fs, children)
+                    .into_iter()
+                    .try_for_each(|child| {
+                        writeln!(f)?;
+                        self.fmt_node(f, child, depth + 1)
+                    })
+            }
+        }
+    }
+}
+
+impl<'f, 'a> Display for TreeReport<'f, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_node(f, self.fs.root(), 0)
+    }
+}
+
+/// Directory totals listed like `du`, see [`Filesystem::du`].
+pub struct DuReport<'f, 'a> {
+    fs: &'f Filesystem<'a>,
+    options: ReportOptions,
+}
+
+impl<'f, 'a> DuReport<'f, 'a> {
+    // Sub-directories come before their parent, as with du.
+    fn collect(&self, id: NodeId, depth: usize, lines: &mut Vec<NodeId>) {
+        let children = self.fs.node(id).children();
+        if self.options.shows_depth(depth + 1) {
+            for child in self.options.sorted(self.fs, children) {
+                if self.fs.node(child).is_dir() {
+                    self.collect(child, depth + 1, lines);
+                }
+            }
+        }
+        lines.push(id);
+    }
+}
+
+impl<'f, 'a> Display for DuReport<'f, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = Vec::new();
+        self.collect(self.fs.root(), 0, &mut lines);
+        match self.options.sort_by {
+            SortBy::Listing => {}
+            // Sort on the whole path rather than on the last name.
+            SortBy::Name => lines.sort_by_cached_key(|&id| self.fs.path(id)),
+            SortBy::Size => lines = self.options.sorted(self.fs, &lines),
+        }
+        lines.iter().enumerate().try_for_each(|(i, &id)| {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let size = self.options.size(self.fs.size(id));
+            write!(f, "{size}\t{}", self.fs.path(id))
+        })
+    }
+}
+
+impl<'a> Filesystem<'a> {
+    /// Renders the tree with one node per line, indented by depth.
+    pub fn tree(&self, options: ReportOptions) -> TreeReport<'_, 'a> {
+        TreeReport { fs: self, options }
+    }
+
+    /// Renders the total size of every directory, one per line. A
+    /// `max_depth` of 0 only shows the root, like `du -s`.
+    pub fn du(&self, options: ReportOptions) -> DuReport<'_, 'a> {
+        DuReport { fs: self, options }
+    }
+}
+
+impl<'a> Display for Filesystem<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tree(ReportOptions::default()).fmt(f)
+    }
+}
@@ -0,0 +1,152 @@
+use std::{iter, str::Lines};
+
+use itertools::Itertools;
+use log::warn;
+use thiserror::Error;
+
+use crate::{Filesystem, FilesystemError, NodeId};
+
+#[derive(Error, PartialEq, Eq, Debug)]
+pub enum TranscriptError {
+    #[error("line {line}: invalid ls output {output:?}")]
+    InvalidLsOutput { line: usize, output: String },
+    #[error("line {line}: {source}")]
+    Filesystem {
+        line: usize,
+        #[source]
+        source: FilesystemError,
+    },
+}
+
+pub(crate) struct CLIParser<'a, 'b> {
+    input: &'a mut Lines<'b>,
+}
+
+impl<'a, 'b> CLIParser<'a, 'b> {
+    pub(crate) fn new(input: &'a mut Lines<'b>) -> Self {
+        Self { input }
+    }
+
+    pub(crate) fn parse(&mut self) -> Result<Filesystem<'b>, TranscriptError> {
+        let mut fs = Filesystem::new();
+        let root = fs.root();
+        Command::parse_commands(self.input)?
+            .iter()
+            .try_fold(root, |pwd, (line, command)| {
+                command
+                    .deduce_fs(&mut fs, pwd)
+                    .map_err(|source| TranscriptError::Filesystem {
+                        line: *line,
+                        source,
+                    })
+            })?;
+        Ok(fs)
+    }
+}
+
+enum Command<'a> {
+    CD { path: &'a str },
+    LS { output: Vec<LsOutput<'a>> },
+}
+
+enum LsOutput<'a> {
+    Dir { name: &'a str },
+    File { size: usize, name: &'a str },
+}
+
+impl<'a> LsOutput<'a> {
+    fn parse(output: &'a str) -> Option<Self> {
+        use LsOutput::*;
+        // Split only once so names can contain spaces.
+        match output.split_once(' ')? {
+            ("dir", name) => Some(Dir { name }),
+            (size, name) => Some(File {
+                size: size.parse().ok()?,
+                name,
+            }),
+        }
+    }
+}
+
+impl<'a> Command<'a> {
+    const COMMAND_LINE_START: char = '$';
+    const PATH_SEPARATOR: char = '/';
+
+    fn is_command(input: &str) -> bool {
+        input.starts_with(Self::COMMAND_LINE_START)
+    }
+
+    /// Returns the commands with their line number, skipping unknown ones.
+    fn parse_commands(lines: &mut Lines<'a>) -> Result<Vec<(usize, Self)>, TranscriptError> {
+        let mut lines = lines.enumerate().map(|(i, line)| (i + 1, line)).peekable();
+        let mut commands = Vec::new();
+        while let Some((line, command_line)) = lines.next() {
+            let output =
+                iter::from_fn(|| lines.next_if(|(_, o)| !Self::is_command(o))).collect_vec();
+            let Some(command_line) = command_line.strip_prefix(Self::COMMAND_LINE_START) else {
+                if !command_line.trim().is_empty() {
+                    warn!("line {line}: ignoring output without a command");
+                }
+                continue;
+            };
+            let command_line = command_line.trim();
+            let (name, args) = command_line
+                .split_once(' ')
+                .map_or((command_line, ""), |(name, args)| (name, args.trim()));
+            use Command::*;
+            match (name, args) {
+                ("cd", "") => warn!("line {line}: ignoring cd without a path"),
+                ("cd", path) => commands.push((line, CD { path })),
+                ("ls", "") => {
+                    let output = output
+                        .into_iter()
+                        .filter(|(_, o)| !o.trim().is_empty())
+                        .map(|(line, o)| {
+                            LsOutput::parse(o.trim()).ok_or_else(|| {
+                                TranscriptError::InvalidLsOutput {
+                                    line,
+                                    output: o.to_string(),
+                                }
+                            })
+                        })
+                        .try_collect()?;
+                    commands.push((line, LS { output }));
+                }
+                _ => warn!("line {line}: ignoring unknown command {command_line:?}"),
+            }
+        }
+        Ok(commands)
+    }
+
+    fn deduce_fs(&self, fs: &mut Filesystem<'a>, pwd: NodeId) -> Result<NodeId, FilesystemError> {
+        use Command::*;
+        match self {
+            CD { path } => {
+                let start = if path.starts_with(Self::PATH_SEPARATOR) {
+                    fs.root()
+                } else {
+                    pwd
+                };
+                path.split(Self::PATH_SEPARATOR)
+                    .try_fold(start, |dir, segment| match segment {
+                        "" | "." => Ok(dir),
+                        // The root is its own parent.
+                        ".." => Ok(fs.parent(dir).unwrap_or(dir)),
+                        // Move into target or create it in our FS.
+                        name => fs.add_dir(dir, name),
+                    })
+            }
+            LS { output } => {
+                output.iter().try_for_each(|out| {
+                    use LsOutput::*;
+                    match out {
+                        Dir { name } => fs.add_dir(pwd, name),
+                        File { size, name } => fs.add_file(pwd, name, *size),
+                    }
+                    .map(|_| ())
+                })?;
+                Ok(pwd)
+            }
+        }
+    }
+}
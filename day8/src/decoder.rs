@@ -0,0 +1,107 @@
+use thiserror::Error;
+
+/// How the trees of a row are written in the input.
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum CellDecoder {
+    /// One digit per tree, from 0 to 9.
+    #[default]
+    Digits,
+    /// One lowercase letter per tree, from `a` (0) to `z` (25).
+    Letters,
+    /// Integers separated by whitespace, from 0 to `max`.
+    Integers { max: u16 },
+}
+
+impl CellDecoder {
+    pub fn max_height(self) -> u16 {
+        match self {
+            CellDecoder::Digits => 9,
+            CellDecoder::Letters => 25,
+            CellDecoder::Integers { max } => max,
+        }
+    }
+
+    /// Appends the heights of the trees of row `y` to `heights`, returning
+    /// how many trees the row has.
+    pub(crate) fn decode_row(
+        self,
+        row: &str,
+        y: usize,
+        heights: &mut Vec<u16>,
+    ) -> Result<usize, ParseForestError> {
+        let start = heights.len();
+        let mut push = |x: usize, height: Result<TreeHeight, TryFromTreeHeightError>| {
+            height
+                .map(|height| heights.push(height.into()))
+                .map_err(|source| ParseForestError::InvalidHeight { x, y, source })
+        };
+        match self {
+            CellDecoder::Digits | CellDecoder::Letters => {
+                for (x, c) in row.chars().enumerate() {
+                    push(x, self.decode_char(c))?;
+                }
+            }
+            CellDecoder::Integers { max } => {
+                for (x, cell) in row.split_whitespace().enumerate() {
+                    let height = cell
+                        .parse()
+                        .map_err(TryFromTreeHeightError::from)
+                        .and_then(|value| TreeHeight::new(value, max));
+                    push(x, height)?;
+                }
+            }
+        }
+        Ok(heights.len() - start)
+    }
+
+    fn decode_char(self, c: char) -> Result<TreeHeight, TryFromTreeHeightError> {
+        let value = match self {
+            CellDecoder::Letters => c
+                .is_ascii_lowercase()
+                .then(|| u32::from(c) - u32::from('a')),
+            _ => c.to_digit(10),
+        };
+        let value = value.ok_or(TryFromTreeHeightError::InvalidChar(c))?;
+        TreeHeight::new(value, self.max_height())
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub(crate) struct TreeHeight(u16);
+
+impl TreeHeight {
+    fn new(value: u32, max: u16) -> Result<Self, TryFromTreeHeightError> {
+        match u16::try_from(value) {
+            Ok(value) if value <= max => Ok(TreeHeight(value)),
+            _ => Err(TryFromTreeHeightError::TooBig { value, max }),
+        }
+    }
+}
+
+impl From<TreeHeight> for u16 {
+    fn from(value: TreeHeight) -> Self {
+        value.0
+    }
+}
+
+#[derive(Error, PartialEq, Eq, Debug)]
+pub enum TryFromTreeHeightError {
+    #[error("{value} is higher than the max tree height (i.e. {max})")]
+    TooBig { value: u32, max: u16 },
+    #[error("{0:?} is not a tree height")]
+    InvalidChar(char),
+    #[error(transparent)]
+    ParseIntError(#[from] std::num::ParseIntError),
+}
+
+#[derive(Error, PartialEq, Eq, Debug)]
+pub enum ParseForestError {
+    #[error("invalid tree at x={x}, y={y}: {source}")]
+    InvalidHeight {
+        x: usize,
+        y: usize,
+        source: TryFromTreeHeightError,
+    },
+    #[error("row {y} has {len} trees instead of {width}")]
+    NotRectangular { y: usize, len: usize, width: usize },
+}
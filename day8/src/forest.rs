@@ -1,170 +1,264 @@
-use std::str::FromStr;
+/// Which directions a line of sight can follow.
+#[derive(PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub enum SightModel {
+    /// North, south, east and west.
+    #[default]
+    Cardinal,
+    /// The cardinal directions and the diagonals.
+    Compass,
+}
+
+impl SightModel {
+    const CARDINALS: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+    const DIAGONALS: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
 
-use itertools::Itertools;
-use thiserror::Error;
+    fn directions(self) -> impl Iterator<Item = (isize, isize)> {
+        let diagonals = match self {
+            SightModel::Cardinal => &[][..],
+            SightModel::Compass => &Self::DIAGONALS[..],
+        };
+        Self::CARDINALS.into_iter().chain(diagonals.iter().copied())
+    }
+}
 
-pub(crate) struct Forest {
-    grid: Vec<Vec<Tree>>,
+/// Tree heights stored row after row.
+#[derive(Debug)]
+pub struct Forest {
+    heights: Box<[u16]>,
+    width: usize,
+    height: usize,
 }
 
 impl Forest {
-    pub(crate) fn new(grid: Vec<Vec<Tree>>) -> Self {
-        Forest { grid }
+    pub(crate) fn new(heights: Box<[u16]>, width: usize) -> Self {
+        let height = heights.len().checked_div(width).unwrap_or(0);
+        assert_eq!(
+            width * height,
+            heights.len(),
+            "rows must have the same length"
+        );
+        Forest {
+            heights,
+            width,
+            height,
+        }
     }
 
-    pub(crate) fn iter_rows_then_columns(&self) -> RowsThenColumnIterator {
-        RowsThenColumnIterator::new(self)
+    pub fn width(&self) -> usize {
+        self.width
     }
 
-    pub(crate) fn tree_is_on_edge(&self, tree: &Tree) -> bool {
-        let Position { x, y } = tree.position();
-        x == 0
-            || y == 0
-            || y == self.grid.len() - 1
-            || self.grid.get(0).is_some_and(|row| x == row.len() - 1)
+    pub fn height(&self) -> usize {
+        self.height
     }
 
-    pub(crate) fn tree_scenic_score(&self, tree: &Tree) -> usize {
-        if self.tree_is_on_edge(tree) {
-            return 0;
-        }
-        let Position { x, y } = tree.position();
-        // Check the right viewing distance.
-        let tree_row = self.grid.get(y).expect("safe");
-        let east_vd = (x + 1..tree_row.len())
-            .filter_map(|i| tree_row.get(i))
-            .take_while_inclusive(|other_tree| tree.height() > other_tree.height())
-            .count();
-        // Check the left viewing distance.
-        let west_vd = (0..x)
-            .rev()
-            .filter_map(|i| tree_row.get(i))
-            .take_while_inclusive(|other_tree| tree.height() > other_tree.height())
-            .count();
-        // Check the bottom viewing distance.
-        let south_vd = (y + 1..self.grid.len())
-            .filter_map(|i| self.grid.get(i).and_then(|col| col.get(x)))
-            .take_while_inclusive(|other_tree| tree.height() > other_tree.height())
-            .count();
-        // Check the top viewing distance.
-        let north_vd = (0..y)
-            .rev()
-            .filter_map(|i| self.grid.get(i).and_then(|col| col.get(x)))
-            .take_while_inclusive(|other_tree| tree.height() > other_tree.height())
-            .count();
-
-        east_vd * west_vd * south_vd * north_vd
+    pub fn tree_height(&self, x: usize, y: usize) -> u16 {
+        self.heights[self.index(x, y)]
     }
-}
 
-pub(crate) struct RowsThenColumnIterator<'a> {
-    forest: &'a Forest,
-    row_len: usize,
-    current_line: usize,
-}
+    #[inline(always)]
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
 
-impl<'a> RowsThenColumnIterator<'a> {
-    fn new(forest: &'a Forest) -> Self {
-        Self {
-            forest,
-            row_len: forest.grid.len(),
-            current_line: 0,
-        }
+    // Next position in a direction, if still in the grid.
+    #[inline(always)]
+    fn step(&self, (x, y): (usize, usize), (dx, dy): (isize, isize)) -> Option<(usize, usize)> {
+        let x = x.checked_add_signed(dx).filter(|&x| x < self.width)?;
+        let y = y.checked_add_signed(dy).filter(|&y| y < self.height)?;
+        Some((x, y))
     }
-}
 
-impl<'a> Iterator for RowsThenColumnIterator<'a> {
-    type Item = Vec<Tree>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let line = self.current_line;
-        self.current_line += 1;
-        if line < self.row_len {
-            // Return a row.
-            self.forest.grid.get(line).cloned()
-        } else {
-            // Build and return a col.
-            let col_nb = line - self.row_len;
-            self.forest.grid.iter().fold(Some(Vec::new()), |acc, row| {
-                acc.and_then(|mut v| {
-                    row.get(col_nb).cloned().map(|t| {
-                        v.push(t);
-                        v
-                    })
-                })
-            })
+    // Positions from `start` to the edge in a direction, `start` excluded.
+    fn walk(
+        &self,
+        start: (usize, usize),
+        direction: (isize, isize),
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        std::iter::successors(self.step(start, direction), move |&p| {
+            self.step(p, direction)
+        })
+    }
+
+    /// Marks the trees visible from outside the grid, sweeping it once from
+    /// each side while tracking the highest tree met so far.
+    pub fn visible_trees(&self, model: SightModel) -> TreeSet {
+        let mut visible = TreeSet::new(self.width, self.height);
+        // Sweep from the west then from the east, row by row.
+        for y in 0..self.height {
+            let row = self.index(0, y)..self.index(0, y + 1);
+            let mut highest = None;
+            for i in row.clone() {
+                Self::check(&mut visible, &mut highest, i, self.heights[i]);
+            }
+            highest = None;
+            for i in row.rev() {
+                Self::check(&mut visible, &mut highest, i, self.heights[i]);
+            }
         }
+        // Sweep from the north then from the south, a whole row at once.
+        let mut highest = vec![None; self.width];
+        for y in 0..self.height {
+            for (x, highest) in highest.iter_mut().enumerate() {
+                let i = self.index(x, y);
+                Self::check(&mut visible, highest, i, self.heights[i]);
+            }
+        }
+        highest.fill(None);
+        for y in (0..self.height).rev() {
+            for (x, highest) in highest.iter_mut().enumerate() {
+                let i = self.index(x, y);
+                Self::check(&mut visible, highest, i, self.heights[i]);
+            }
+        }
+        if model == SightModel::Compass {
+            // Every diagonal starts on the side it is entered from.
+            for direction in SightModel::DIAGONALS {
+                let opposite = (-direction.0, -direction.1);
+                for y in 0..self.height {
+                    for x in 0..self.width {
+                        if self.step((x, y), opposite).is_some() {
+                            continue;
+                        }
+                        let mut highest = None;
+                        let line = std::iter::once((x, y)).chain(self.walk((x, y), direction));
+                        for (x, y) in line {
+                            let i = self.index(x, y);
+                            Self::check(&mut visible, &mut highest, i, self.heights[i]);
+                        }
+                    }
+                }
+            }
+        }
+        visible
     }
-}
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-pub(crate) struct Tree {
-    height: TreeHeight,
-    position: Position,
-}
+    /// Marks the trees seen by an observer at `position` whose eyes are at
+    /// `eye_height`, the observer's own tree excluded.
+    ///
+    /// Looking along each direction, a tree is seen when its top rises above
+    /// the line of sight to every nearer tree.
+    pub fn visible_from(
+        &self,
+        position: (usize, usize),
+        eye_height: u32,
+        model: SightModel,
+    ) -> TreeSet {
+        let mut visible = TreeSet::new(self.width, self.height);
+        let eye_height = i64::from(eye_height);
+        for direction in model.directions() {
+            // Steepest slope seen so far, as rise over distance.
+            let mut steepest: Option<(i64, i64)> = None;
+            for (distance, (x, y)) in (1..).zip(self.walk(position, direction)) {
+                let rise = i64::from(self.tree_height(x, y)) - eye_height;
+                if steepest.is_none_or(|(r, d)| rise * d > r * distance) {
+                    visible.insert(self.index(x, y));
+                    steepest = Some((rise, distance));
+                }
+            }
+        }
+        visible
+    }
 
-impl Tree {
-    pub(crate) fn new(height: TreeHeight, position: (usize, usize)) -> Self {
-        Self {
-            height,
-            position: position.into(),
+    #[inline(always)]
+    fn check(visible: &mut TreeSet, highest: &mut Option<u16>, i: usize, height: u16) {
+        if highest.is_none_or(|h| height > h) {
+            visible.insert(i);
+            *highest = Some(height);
         }
     }
 
-    pub(crate) fn height(&self) -> TreeHeight {
-        self.height
+    /// Computes the scenic score of every tree, row after row.
+    ///
+    /// Sweeping a line while keeping a stack of the trees not yet hidden by a
+    /// taller or equal one gives, for each tree, the closest one blocking its
+    /// view backward, so every line is processed in linear time.
+    pub fn scenic_scores(&self) -> Box<[usize]> {
+        let mut scores = vec![1; self.heights.len()].into_boxed_slice();
+        let mut stack = Vec::new();
+        for y in 0..self.height {
+            let row = self.index(0, y)..self.index(0, y + 1);
+            self.multiply_viewing_distances(&mut scores, &mut stack, row.clone());
+            self.multiply_viewing_distances(&mut scores, &mut stack, row.rev());
+        }
+        for x in 0..self.width {
+            let column = (0..self.height).map(|y| self.index(x, y));
+            self.multiply_viewing_distances(&mut scores, &mut stack, column.clone());
+            self.multiply_viewing_distances(&mut scores, &mut stack, column.rev());
+        }
+        scores
     }
 
-    pub(crate) fn position(&self) -> Position {
-        self.position
+    // Multiplies the score of each tree of the line by its viewing distance
+    // toward the start of the line.
+    fn multiply_viewing_distances(
+        &self,
+        scores: &mut [usize],
+        stack: &mut Vec<(usize, u16)>,
+        line: impl Iterator<Item = usize>,
+    ) {
+        stack.clear();
+        for (distance_from_edge, i) in line.enumerate() {
+            let height = self.heights[i];
+            while stack.last().is_some_and(|&(_, h)| h < height) {
+                stack.pop();
+            }
+            // Either the blocking tree or the edge.
+            let viewing_distance = stack.last().map_or(distance_from_edge, |&(blocking, _)| {
+                distance_from_edge - blocking
+            });
+            scores[i] *= viewing_distance;
+            stack.push((distance_from_edge, height));
+        }
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
-pub(crate) struct Position {
-    x: usize,
-    y: usize,
+/// A set of trees of a forest, stored as one bit per tree.
+#[derive(Debug)]
+pub struct TreeSet {
+    words: Box<[u64]>,
+    width: usize,
 }
 
-impl From<(usize, usize)> for Position {
-    fn from(value: (usize, usize)) -> Self {
-        let (x, y) = value;
-        Position { x, y }
+impl TreeSet {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            words: vec![0; (width * height).div_ceil(64)].into_boxed_slice(),
+            width,
+        }
     }
-}
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
-pub(crate) struct TreeHeight(u8);
-
-impl TreeHeight {
-    const MAX: u8 = 9;
-}
-
-#[derive(Error, Debug)]
-pub enum TryFromTreeHeightError {
-    #[error("{0} is higher than the max tree height (i.e. {})", TreeHeight::MAX)]
-    TooBig(u8),
-    #[error(transparent)]
-    ParseIntError(#[from] std::num::ParseIntError),
-}
+    #[inline(always)]
+    fn insert(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
 
-impl TryFrom<u8> for TreeHeight {
-    type Error = TryFromTreeHeightError;
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        let i = y * self.width + x;
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
 
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        if value < 10 {
-            Ok(TreeHeight(value))
-        } else {
-            Err(TryFromTreeHeightError::TooBig(value))
-        }
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
     }
-}
 
-impl FromStr for TreeHeight {
-    type Err = TryFromTreeHeightError;
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let u = s.parse::<u8>()?;
-        u.try_into()
+    /// Iterates over the `(x, y)` positions of the trees, row after row.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let width = self.width;
+        self.words.iter().enumerate().flat_map(move |(i, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                (word != 0).then(|| {
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    let index = i * 64 + bit;
+                    (index % width, index / width)
+                })
+            })
+        })
     }
 }
@@ -0,0 +1,195 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use thiserror::Error;
+
+use crate::{Forest, SightModel};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum HeatmapMode {
+    /// The tree heights, from the lowest to the highest tree of the forest.
+    Height,
+    /// Trees visible from outside in green, hidden ones in dark grey.
+    Visibility(SightModel),
+    /// Scenic scores on a logarithmic scale, as they span many magnitudes.
+    ScenicScore,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    /// Guesses the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Error, PartialEq, Eq, Debug)]
+pub enum HeatmapError {
+    #[error("a {width}x{height} forest scaled {scale} times is too large for an image")]
+    TooLarge {
+        width: usize,
+        height: usize,
+        scale: usize,
+    },
+}
+
+/// An RGB image with one square of `scale` pixels per tree.
+pub struct Heatmap {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Heatmap {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let i = (y * self.width + x) * 3;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+
+    /// Writes a binary PPM (P6) image.
+    pub fn write_ppm(&self, mut writer: impl Write) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(&self.pixels)
+    }
+
+    pub fn write_png(&self, writer: impl Write) -> io::Result<()> {
+        let to_io_error = io::Error::other;
+        let width = u32::try_from(self.width).map_err(to_io_error)?;
+        let height = u32::try_from(self.height).map_err(to_io_error)?;
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(writer.finish()?)
+    }
+
+    /// Saves the image in the format matching the file extension.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let format = ImageFormat::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown image format for {}", path.display()),
+            )
+        })?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        match format {
+            ImageFormat::Ppm => self.write_ppm(&mut writer)?,
+            ImageFormat::Png => self.write_png(&mut writer)?,
+        }
+        writer.flush()
+    }
+}
+
+// Maps a value between 0 and 1 to a dark blue, green, yellow gradient.
+fn gradient(t: f64) -> [u8; 3] {
+    const STOPS: [[f64; 3]; 3] = [
+        [20.0, 30.0, 80.0],
+        [30.0, 150.0, 60.0],
+        [250.0, 230.0, 50.0],
+    ];
+
+    let t = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let i = (t as usize).min(STOPS.len() - 2);
+    let t = t - i as f64;
+    let (from, to) = (STOPS[i], STOPS[i + 1]);
+    [0, 1, 2].map(|c| (from[c] + (to[c] - from[c]) * t).round() as u8)
+}
+
+impl Forest {
+    pub fn heatmap(&self, mode: HeatmapMode, scale: usize) -> Result<Heatmap, HeatmapError> {
+        let scale = scale.max(1);
+        // The image dimensions and its number of bytes.
+        let size = || {
+            let width = self.width().checked_mul(scale)?;
+            let height = self.height().checked_mul(scale)?;
+            Some((width, height, width.checked_mul(height)?.checked_mul(3)?))
+        };
+        let (width, height, len) = size().ok_or(HeatmapError::TooLarge {
+            width: self.width(),
+            height: self.height(),
+            scale,
+        })?;
+        let colors = match mode {
+            HeatmapMode::Height => {
+                // An empty forest has no tree to color.
+                let (min, max) = self.height_range().unwrap_or((0, 0));
+                let span = f64::from(max - min).max(1.0);
+                self.cells()
+                    .map(|(x, y)| gradient(f64::from(self.tree_height(x, y) - min) / span))
+                    .collect::<Vec<_>>()
+            }
+            HeatmapMode::Visibility(model) => {
+                let visible = self.visible_trees(model);
+                self.cells()
+                    .map(|(x, y)| {
+                        if visible.contains(x, y) {
+                            [60, 200, 70]
+                        } else {
+                            [40, 40, 40]
+                        }
+                    })
+                    .collect()
+            }
+            HeatmapMode::ScenicScore => {
+                let scores = self.scenic_scores();
+                let max = scores.iter().copied().max().unwrap_or(0);
+                let span = (max as f64).ln_1p().max(1.0);
+                scores
+                    .iter()
+                    .map(|&score| gradient((score as f64).ln_1p() / span))
+                    .collect()
+            }
+        };
+
+        let mut pixels = Vec::with_capacity(len);
+        for y in 0..height {
+            let row = &colors[(y / scale) * self.width()..][..self.width()];
+            for color in row {
+                for _ in 0..scale {
+                    pixels.extend_from_slice(color);
+                }
+            }
+        }
+        Ok(Heatmap {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.height()).flat_map(move |y| (0..self.width()).map(move |x| (x, y)))
+    }
+
+    // The lowest and highest trees, if any.
+    fn height_range(&self) -> Option<(u16, u16)> {
+        self.cells()
+            .map(|(x, y)| self.tree_height(x, y))
+            .fold(None, |range, h| {
+                let (min, max) = range.unwrap_or((h, h));
+                Some((min.min(h), max.max(h)))
+            })
+    }
+}
@@ -1,79 +1,65 @@
-use std::{collections::HashSet, str::Lines};
+use std::str::Lines;
 
-mod forest;
-use forest::*;
+pub mod decoder;
+pub use decoder::{CellDecoder, ParseForestError, TryFromTreeHeightError};
+pub mod forest;
+pub use forest::{Forest, SightModel, TreeSet};
+pub mod heatmap;
+pub use heatmap::{Heatmap, HeatmapError, HeatmapMode, ImageFormat};
 
 struct ForestParser<'a, 'b>
 where
     'a: 'b,
 {
     lines: &'b mut Lines<'a>,
+    decoder: CellDecoder,
 }
 
 impl<'a, 'b> ForestParser<'a, 'b>
 where
     'a: 'b,
 {
-    fn new(lines: &'b mut Lines<'a>) -> Self {
-        Self { lines }
+    fn new(lines: &'b mut Lines<'a>, decoder: CellDecoder) -> Self {
+        Self { lines, decoder }
     }
 
-    fn parse(self) -> Forest {
-        let grid = self
-            .lines
-            .enumerate()
-            .map(|(y, row)| {
-                row.chars()
-                    .enumerate()
-                    .map(|(x, c)| {
-                        let height = c.to_string().parse().expect("safe");
-                        Tree::new(height, (x, y))
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .collect();
-        Forest::new(grid)
+    fn parse(self) -> Result<Forest, ParseForestError> {
+        let mut heights = Vec::new();
+        let mut width = None;
+        for (y, row) in self.lines.enumerate() {
+            let len = self.decoder.decode_row(row, y, &mut heights)?;
+            match width {
+                None => width = Some(len),
+                Some(width) if width != len => {
+                    return Err(ParseForestError::NotRectangular { y, len, width })
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(Forest::new(heights.into_boxed_slice(), width.unwrap_or(0)))
     }
 }
 
-fn get_forest_and_visible_trees(mut input: Lines) -> (Forest, HashSet<Tree>) {
-    let parser = ForestParser::new(&mut input);
-    let forest = parser.parse();
-    let visible_trees = forest
-        // Iterate over all rows and columns.
-        .iter_rows_then_columns()
-        .fold(HashSet::<Tree>::new(), |mut set, line| {
-            let mut highest_tree = None;
-            line.iter().for_each(|tree| {
-                if highest_tree.is_some_and(|h| tree.height() > h) || highest_tree.is_none() {
-                    set.insert(tree.clone());
-                    highest_tree = Some(tree.height())
-                }
-            });
-            highest_tree = None;
-            line.iter().rev().for_each(|tree| {
-                if highest_tree.is_some_and(|h| tree.height() > h) || highest_tree.is_none() {
-                    set.insert(tree.clone());
-                    highest_tree = Some(tree.height())
-                }
-            });
-            set
-        });
-    (forest, visible_trees)
+/// Parses a forest written with one digit per tree.
+pub fn parse_forest(input: Lines) -> Forest {
+    parse_forest_with(input, CellDecoder::Digits).expect("safe")
+}
+
+pub fn parse_forest_with(
+    mut input: Lines,
+    decoder: CellDecoder,
+) -> Result<Forest, ParseForestError> {
+    ForestParser::new(&mut input, decoder).parse()
 }
 
 pub fn solve_part1(input: Lines) -> usize {
-    let (_, visible_tree_set) = get_forest_and_visible_trees(input);
-    visible_tree_set.len()
+    let forest = parse_forest(input);
+    forest.visible_trees(SightModel::Cardinal).len()
 }
 
 pub fn solve_part2(input: Lines) -> usize {
-    let (forest, visible_tree_set) = get_forest_and_visible_trees(input);
-    visible_tree_set
-        .iter()
-        .map(|tree| forest.tree_scenic_score(tree))
-        .max()
-        .expect("safe")
+    let forest = parse_forest(input);
+    forest.scenic_scores().iter().copied().max().expect("safe")
 }
 
 #[cfg(test)]
@@ -95,4 +81,190 @@ mod tests {
     fn test_part2() {
         assert_eq!(solve_part2(INPUT.lines()), 8);
     }
+
+    #[test]
+    fn test_rectangular_forest() {
+        let input = "99999
+91919
+99999";
+        assert_eq!(solve_part1(input.lines()), 12);
+        assert_eq!(solve_part1("5".lines()), 1);
+    }
+
+    #[test]
+    fn test_scenic_scores() {
+        let forest = parse_forest(INPUT.lines());
+        let scores = forest.scenic_scores();
+        assert_eq!(scores[5 + 2], 4);
+        assert_eq!(scores[3 * 5 + 2], 8);
+        // Edge trees see nothing in at least one direction.
+        assert!((0..5).all(|i| scores[i] == 0 && scores[i * 5] == 0));
+        // A tree hidden from outside can have the best view.
+        let input = "9999999
+9111119
+9111119
+9115119
+9111119
+9111119
+9999999";
+        assert_eq!(solve_part1(input.lines()), 24);
+        assert_eq!(solve_part2(input.lines()), 81);
+    }
+
+    #[test]
+    fn test_sight_models() {
+        let forest = parse_forest(INPUT.lines());
+        let cardinal = forest.visible_trees(SightModel::Cardinal);
+        let compass = forest.visible_trees(SightModel::Compass);
+        assert!(cardinal.iter().all(|(x, y)| compass.contains(x, y)));
+        // The middle tree can only be seen from the corners.
+        let forest = parse_forest("090\n959\n090".lines());
+        assert!(!forest.visible_trees(SightModel::Cardinal).contains(1, 1));
+        assert!(forest.visible_trees(SightModel::Compass).contains(1, 1));
+
+        let flat = parse_forest("11111\n11111\n11111\n11111\n11111".lines());
+        assert_eq!(flat.visible_from((2, 2), 1, SightModel::Cardinal).len(), 4);
+        assert_eq!(flat.visible_from((2, 2), 1, SightModel::Compass).len(), 8);
+        let from_above = flat.visible_from((2, 2), 5, SightModel::Compass);
+        assert_eq!(from_above.len(), 16);
+        assert!(!from_above.contains(2, 2) && !from_above.contains(1, 0));
+        // Trees below the line of sight to a nearer one stay hidden.
+        let row = parse_forest("1159111".lines());
+        let visible = row.visible_from((0, 0), 1, SightModel::Cardinal);
+        assert_eq!(visible.iter().collect::<Vec<_>>(), [(1, 0), (2, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let forest = parse_forest(INPUT.lines());
+        let heatmap = forest.heatmap(HeatmapMode::Height, 3).unwrap();
+        assert_eq!((heatmap.width(), heatmap.height()), (15, 15));
+        // Every tree is a square of a single color.
+        assert_eq!(heatmap.pixel(0, 0), heatmap.pixel(2, 2));
+        // The lowest and highest trees get both ends of the gradient.
+        assert_eq!(heatmap.pixel(3, 0), [20, 30, 80]);
+        assert_eq!(heatmap.pixel(12, 9), [250, 230, 50]);
+
+        let visibility = forest
+            .heatmap(HeatmapMode::Visibility(SightModel::Cardinal), 1)
+            .unwrap();
+        assert_ne!(visibility.pixel(1, 1), visibility.pixel(3, 3));
+        assert_eq!(visibility.pixel(3, 3), visibility.pixel(2, 2));
+        let scores = forest.heatmap(HeatmapMode::ScenicScore, 1).unwrap();
+        assert_eq!(scores.pixel(2, 3), [250, 230, 50]);
+        assert_eq!(scores.pixel(0, 0), [20, 30, 80]);
+
+        let mut ppm = Vec::new();
+        scores.write_ppm(&mut ppm).unwrap();
+        assert!(ppm.starts_with(b"P6\n5 5\n255\n"));
+        assert_eq!(ppm.len(), 11 + 5 * 5 * 3);
+        let mut png = Vec::new();
+        scores.write_png(&mut png).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        assert_eq!(
+            ImageFormat::from_path("forest.PNG".as_ref()),
+            Some(ImageFormat::Png)
+        );
+
+        let empty = parse_forest("".lines()).heatmap(HeatmapMode::Height, 4);
+        assert_eq!(empty.map(|heatmap| heatmap.width()), Ok(0));
+        assert_eq!(
+            forest.heatmap(HeatmapMode::Height, usize::MAX).err(),
+            Some(HeatmapError::TooLarge {
+                width: 5,
+                height: 5,
+                scale: usize::MAX
+            })
+        );
+    }
+
+    #[test]
+    fn test_ragged_forest() {
+        // Same number of trees as a 3x3 grid, but not in rows of 3.
+        let mut input = "303\n2551\n65".lines();
+        assert_eq!(
+            ForestParser::new(&mut input, CellDecoder::Digits)
+                .parse()
+                .err(),
+            Some(ParseForestError::NotRectangular {
+                y: 1,
+                len: 4,
+                width: 3
+            })
+        );
+        let mut input = "303\n255\n6".lines();
+        assert!(ForestParser::new(&mut input, CellDecoder::Digits)
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn test_cell_decoders() {
+        let letters = parse_forest_with("azb\nbcd".lines(), CellDecoder::Letters).unwrap();
+        assert_eq!(letters.tree_height(1, 0), 25);
+        assert_eq!(letters.visible_trees(SightModel::Cardinal).len(), 6);
+        let integers = "10 200  3\n 4 5 250";
+        let forest =
+            parse_forest_with(integers.lines(), CellDecoder::Integers { max: 250 }).unwrap();
+        assert_eq!((forest.width(), forest.height()), (3, 2));
+        assert_eq!(forest.tree_height(1, 0), 200);
+        assert_eq!(forest.tree_height(2, 1), 250);
+        let wide = CellDecoder::Integers { max: u16::MAX };
+        let forest = parse_forest_with("1000 65535\n0 300".lines(), wide).unwrap();
+        assert_eq!(forest.tree_height(1, 0), 65535);
+        assert_eq!(forest.visible_trees(SightModel::Cardinal).len(), 4);
+        assert_eq!(
+            parse_forest_with("70000".lines(), wide).unwrap_err(),
+            ParseForestError::InvalidHeight {
+                x: 0,
+                y: 0,
+                source: TryFromTreeHeightError::TooBig {
+                    value: 70000,
+                    max: u16::MAX
+                },
+            }
+        );
+
+        assert_eq!(
+            parse_forest_with("012\n3a5".lines(), CellDecoder::Digits).unwrap_err(),
+            ParseForestError::InvalidHeight {
+                x: 1,
+                y: 1,
+                source: TryFromTreeHeightError::InvalidChar('a'),
+            }
+        );
+        assert_eq!(
+            parse_forest_with("ab\nB".lines(), CellDecoder::Letters).unwrap_err(),
+            ParseForestError::InvalidHeight {
+                x: 0,
+                y: 1,
+                source: TryFromTreeHeightError::InvalidChar('B'),
+            }
+        );
+        let err = parse_forest_with("1 2\n3 300".lines(), CellDecoder::Integers { max: 99 });
+        assert_eq!(
+            err.unwrap_err(),
+            ParseForestError::InvalidHeight {
+                x: 1,
+                y: 1,
+                source: TryFromTreeHeightError::TooBig {
+                    value: 300,
+                    max: 99
+                },
+            }
+        );
+        let err = parse_forest_with("1 -2".lines(), CellDecoder::Integers { max: 9 }).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid tree at x=1, y=0: invalid digit found in string"
+        );
+        assert_eq!(
+            parse_forest_with("12\n345".lines(), CellDecoder::Digits).unwrap_err(),
+            ParseForestError::NotRectangular {
+                y: 1,
+                len: 3,
+                width: 2
+            }
+        );
+    }
 }
@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+
+use criterion::{black_box, criterion_group, BenchmarkId, Criterion, Throughput};
+use itertools::Itertools;
+
+use day9::{Direction, Position, Rope, VisitedCells};
+
+// Tail positions of a rope following a pseudo-random walk of `moves` moves.
+fn tail_positions(moves: usize) -> Vec<Position> {
+    use Direction::*;
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut rope = Rope::new(2);
+    (0..moves)
+        .flat_map(|_| {
+            // xorshift64
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let direction = [Left, Right, Up, Down][(state % 4) as usize];
+            std::iter::repeat_n(direction, (state >> 8) as usize % 20 + 1)
+        })
+        .map(|direction| rope.step(direction))
+        .collect()
+}
+
+fn vec_unique(positions: &[Position]) -> usize {
+    let mut visited = vec![Position::default()];
+    visited.extend(positions.iter().copied());
+    visited.iter().unique().count()
+}
+
+fn hash_set(positions: &[Position], capacity: usize) -> usize {
+    let mut visited = HashSet::with_capacity(capacity);
+    visited.insert(Position::default());
+    visited.extend(positions.iter().copied());
+    visited.len()
+}
+
+fn bitmap(positions: &[Position]) -> usize {
+    let mut visited = VisitedCells::from_iter([Position::default()]);
+    visited.extend(positions.iter().copied());
+    visited.len()
+}
+
+type Approach = (&'static str, fn(&[Position]) -> usize);
+
+const APPROACHES: [Approach; 4] = [
+    ("vec_unique", vec_unique),
+    ("hash_set", |positions| hash_set(positions, 0)),
+    ("hash_set_5900", |positions| hash_set(positions, 5900)),
+    ("bitmap", bitmap),
+];
+
+const MOVES: [usize; 2] = [2_000, 200_000];
+
+fn bench_visited(c: &mut Criterion) {
+    let mut group = c.benchmark_group("visited");
+    for moves in MOVES {
+        let positions = tail_positions(moves);
+        group.throughput(Throughput::Elements(positions.len() as u64));
+        for (name, approach) in APPROACHES {
+            group.bench_with_input(BenchmarkId::new(name, moves), &positions, |b, positions| {
+                b.iter(|| approach(black_box(positions)))
+            });
+        }
+    }
+    group.finish();
+}
+
+// Peak heap usage of every approach, each run under its own profiler.
+#[cfg(feature = "dhat-heap")]
+fn profile_heap() {
+    for moves in MOVES {
+        let positions = tail_positions(moves);
+        for (name, approach) in APPROACHES {
+            let _profiler = dhat::Profiler::builder()
+                .file_name(format!("dhat-visited-{name}-{moves}.json"))
+                .build();
+            let count = approach(black_box(&positions));
+            let stats = dhat::HeapStats::get();
+            println!(
+                "{name}/{moves}: {count} cells, peak {} bytes in {} blocks, {} allocations",
+                stats.max_bytes, stats.max_blocks, stats.total_blocks
+            );
+        }
+    }
+}
+
+criterion_group!(benches, bench_visited);
+
+fn main() {
+    #[cfg(feature = "dhat-heap")]
+    profile_heap();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+}
@@ -1,12 +1,16 @@
 use std::{
-    collections::HashSet,
-    io::{BufRead, BufReader},
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{BufReader, BufWriter, Cursor, Write},
+    ops::RangeInclusive,
+    path::Path,
+    sync::Arc,
     time::Duration,
 };
 
-use egui::{Color32, Sense, Slider, Stroke};
+use egui::{ecolor::Hsva, pos2, vec2, Color32, DragValue, Pos2, Rect, Sense, Slider, Stroke, Vec2};
 
-use day9::{get_directions, Direction, Position};
+use day9::{get_directions, Direction, DirectionParseError, Position, Rope, Trace, VisitedCells};
 
 fn main() {
     let options = eframe::NativeOptions {
@@ -16,69 +20,371 @@ fn main() {
     eframe::run_native(
         "AoC 2022 - Day9 part1",
         options,
-        //Box::new(|_cc| Box::new(MyApp::<2>::init())),
-        Box::new(|_cc| Box::new(MyApp::<10>::init())),
+        Box::new(|_cc| Box::new(MyApp::init())),
     )
     .unwrap();
 }
 
-struct MyApp<const N: usize> {
-    directions: Box<dyn Iterator<Item = Direction>>,
+/// How many steps can be undone.
+const HISTORY_LEN: usize = 10_000;
+
+/// The knot counts offered by the slider, traces included.
+const KNOTS: RangeInclusive<usize> = 2..=30;
+
+/// The state before a step, and the cells the step visited for the first
+/// time along with the knot visiting them.
+struct Snapshot {
+    rope: Rope,
+    last_direction: Option<Direction>,
+    new_cells: Vec<(usize, Position)>,
+}
+
+/// What is drawn below the rope.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Overlay {
+    TailVisits,
+    /// The cells visited by every knot, one color per knot.
+    KnotTrails,
+    /// How many times the tail moved into each cell.
+    Heatmap,
+}
+
+// A distinct color for each of `knots` knots, from the head to the tail.
+fn knot_color(knot: usize, knots: usize) -> Color32 {
+    Hsva::new(knot as f32 / knots as f32, 0.8, 0.9, 1.0).into()
+}
+
+// Maps a value between 0 and 1 to a dark blue, red, yellow gradient.
+fn heat_color(t: f32) -> Color32 {
+    const STOPS: [[f32; 3]; 3] = [
+        [30.0, 30.0, 120.0],
+        [200.0, 30.0, 30.0],
+        [255.0, 230.0, 60.0],
+    ];
+
+    let t = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (t as usize).min(STOPS.len() - 2);
+    let t = t - i as f32;
+    let [r, g, b] = [0, 1, 2].map(|c| (STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * t) as u8);
+    Color32::from_rgb(r, g, b)
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum CameraMode {
+    /// Moved around by dragging and scrolling.
+    Free,
+    FollowHead,
+    /// Zoomed out to show every visited cell and the rope.
+    FitVisited,
+}
+
+/// Maps cells to the screen, `y` going up.
+struct Camera {
+    /// The cell shown at the center of the panel.
+    center: Pos2,
+    /// Pixels per cell.
+    zoom: f32,
+    mode: CameraMode,
+}
+
+impl Camera {
+    const DEFAULT_ZOOM: f32 = 5.0;
+    const MIN_ZOOM: f32 = 0.1;
+    const MAX_ZOOM: f32 = 60.0;
+
+    fn new() -> Self {
+        Self {
+            center: Pos2::ZERO,
+            zoom: Self::DEFAULT_ZOOM,
+            mode: CameraMode::Free,
+        }
+    }
+
+    fn screen_pos(&self, rect: Rect, pos: Position) -> Pos2 {
+        let offset = vec2(
+            pos.x() as f32 - self.center.x,
+            self.center.y - pos.y() as f32,
+        );
+        rect.center() + offset * self.zoom
+    }
+
+    // The cell coordinates under a point of the screen.
+    fn cell_at(&self, rect: Rect, point: Pos2) -> Pos2 {
+        let offset = (point - rect.center()) / self.zoom;
+        pos2(self.center.x + offset.x, self.center.y - offset.y)
+    }
+
+    fn pan(&mut self, delta: Vec2) {
+        self.center += vec2(-delta.x, delta.y) / self.zoom;
+    }
+
+    /// Zooms by `factor`, keeping the cell under `point` in place.
+    fn zoom_at(&mut self, rect: Rect, point: Pos2, factor: f32) {
+        let before = self.cell_at(rect, point);
+        self.zoom = (self.zoom * factor).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        self.center += before - self.cell_at(rect, point);
+    }
+
+    fn center_on(&mut self, pos: Position) {
+        self.center = pos2(pos.x() as f32, pos.y() as f32);
+    }
+
+    /// Centers the cells in a panel of `size`, zooming to show them all.
+    fn fit(&mut self, size: Vec2, cells: impl Iterator<Item = Position>) {
+        let Some((min, max)) = cells.fold(None, |bounds: Option<(Pos2, Pos2)>, pos| {
+            let pos = pos2(pos.x() as f32, pos.y() as f32);
+            Some(bounds.map_or((pos, pos), |(min, max)| (min.min(pos), max.max(pos))))
+        }) else {
+            return;
+        };
+        self.center = min + (max - min) / 2.0;
+        // A cell of margin on each side.
+        let cells = max - min + vec2(3.0, 3.0);
+        self.zoom = (size.x / cells.x)
+            .min(size.y / cells.y)
+            .clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+    }
+
+    /// The lowest and highest cells in view.
+    fn visible_cells(&self, rect: Rect) -> (Position, Position) {
+        let (min, max) = (
+            self.cell_at(rect, rect.left_bottom()),
+            self.cell_at(rect, rect.right_top()),
+        );
+        // Float to int casts saturate.
+        (
+            Position::new(min.x.floor() as i16, min.y.floor() as i16),
+            Position::new(max.x.ceil() as i16, max.y.ceil() as i16),
+        )
+    }
+}
+
+struct MyApp {
+    input: Arc<[u8]>,
+    // Replayed instead of the input when opened.
+    trace: Option<Arc<Trace>>,
+    directions: Box<dyn Iterator<Item = Result<Direction, DirectionParseError>>>,
+    // Directions undone by stepping back, the last one on top.
+    undone: Vec<Direction>,
+    history: VecDeque<Snapshot>,
+    step: usize,
     last_direction: Option<Direction>,
-    knots: [Position; N],
-    tail_positions: HashSet<Position>,
+    error: Option<String>,
+    rope: Rope,
+    // The cells visited by each knot, the tail ones being the last.
+    trails: Vec<VisitedCells>,
+    tail_visits: HashMap<Position, u32>,
     // Display settings.
+    camera: Camera,
+    overlay: Overlay,
+    knots: usize,
+    jump_target: usize,
     paused: bool,
     speed: u8,
     show_sidebar: bool,
 }
 
-impl<const N: usize> MyApp<N> {
+impl MyApp {
     fn init() -> Self {
-        let input = std::fs::File::open("./src/input.txt").expect("correct input file");
-        let reader = BufReader::new(input);
-        Self::init_from(reader)
+        let mut app = Self::init_from(Vec::new(), 10);
+        app.open(Path::new("./src/input.txt"));
+        app
     }
 
     #[inline(always)]
-    fn init_from(input: impl BufRead + 'static) -> Self {
-        assert!(N > 1, "invalid N");
-        let directions = get_directions(input);
-
+    fn init_from(input: impl Into<Arc<[u8]>>, knots: usize) -> Self {
+        let input = input.into();
         Self {
-            directions: Box::new(directions),
+            directions: Self::directions(&input, None),
+            input,
+            trace: None,
+            undone: Vec::new(),
+            history: VecDeque::new(),
+            step: 0,
             last_direction: None,
-            knots: [Position::default(); N],
-            tail_positions: HashSet::from([Position::default()]),
+            error: None,
+            rope: Rope::new(knots),
+            trails: Self::start_trails(knots),
+            tail_visits: HashMap::from([(Position::default(), 1)]),
+            camera: Camera::new(),
+            overlay: Overlay::TailVisits,
+            knots,
+            jump_target: 0,
             paused: true,
             speed: 1,
             show_sidebar: true,
         }
     }
 
+    fn start_trails(knots: usize) -> Vec<VisitedCells> {
+        vec![VisitedCells::from_iter([Position::default()]); knots]
+    }
+
+    fn tail_positions(&self) -> &VisitedCells {
+        self.trails.last().expect("a rope has a tail")
+    }
+
+    fn directions(
+        input: &Arc<[u8]>,
+        trace: Option<&Arc<Trace>>,
+    ) -> Box<dyn Iterator<Item = Result<Direction, DirectionParseError>>> {
+        match trace {
+            Some(trace) => {
+                let trace = trace.clone();
+                Box::new((0..trace.len()).map(move |i| Ok(trace.directions()[i])))
+            }
+            None => Box::new(get_directions(BufReader::new(Cursor::new(input.clone())))),
+        }
+    }
+
+    /// Loads another input or trace, starting over.
+    fn open(&mut self, path: &Path) {
+        match std::fs::read(path) {
+            Ok(bytes) => self.load(bytes),
+            Err(e) => self.error = Some(format!("cannot open {}: {e}", path.display())),
+        }
+    }
+
+    fn load(&mut self, bytes: Vec<u8>) {
+        if Trace::is_trace(&bytes) {
+            match Trace::read_from(bytes.as_slice()) {
+                Ok(trace) if !KNOTS.contains(&trace.knots()) => {
+                    let knots = trace.knots();
+                    self.error = Some(format!("cannot replay a trace of {knots} knots"));
+                    return;
+                }
+                Ok(trace) => {
+                    self.knots = trace.knots();
+                    self.trace = Some(Arc::new(trace));
+                }
+                Err(e) => {
+                    self.error = Some(format!("invalid trace: {e}"));
+                    return;
+                }
+            }
+        } else {
+            self.input = bytes.into();
+            self.trace = None;
+        }
+        self.camera = Camera::new();
+        self.restart();
+    }
+
+    /// The whole run with the current knot count, recorded for sharing.
+    fn record_trace(&self) -> Result<Arc<Trace>, DirectionParseError> {
+        if let Some(trace) = &self.trace {
+            return Ok(trace.clone());
+        }
+        let directions = Self::directions(&self.input, None).collect::<Result<Vec<_>, _>>()?;
+        Ok(Arc::new(Trace::record(self.knots, directions)))
+    }
+
+    fn save_trace(&mut self, path: &Path) {
+        let trace = match self.record_trace() {
+            Ok(trace) => trace,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                return;
+            }
+        };
+        let written = File::create(path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            trace.write_to(&mut writer)?;
+            writer.flush()
+        });
+        if let Err(e) = written {
+            self.error = Some(format!("cannot save {}: {e}", path.display()));
+        }
+    }
+
+    /// Starts over with the current input and knot count.
+    fn restart(&mut self) {
+        self.directions = Self::directions(&self.input, self.trace.as_ref());
+        self.undone.clear();
+        self.history.clear();
+        self.step = 0;
+        self.last_direction = None;
+        self.error = None;
+        self.rope = Rope::new(self.knots);
+        self.trails = Self::start_trails(self.knots);
+        self.tail_visits = HashMap::from([(Position::default(), 1)]);
+    }
+
     fn step(&mut self) -> Option<()> {
-        // Update head.
-        self.last_direction = self.directions.next();
-        let direction = self.last_direction?;
-        self.knots[0] = self.knots[0].move_to(direction);
-        let mut previous_knot_position = self.knots[0];
-        // Update in between knots.
-        for i in 1..N - 1 {
-            let knot_pos = self.knots[i];
-            let diff = previous_knot_position - knot_pos;
-            self.knots[i] = knot_pos.move_delta(diff);
-            previous_knot_position = self.knots[i];
-        }
-        // Update tail.
-        let tail_pos = self.knots[N - 1];
-        let diff = previous_knot_position - tail_pos;
-        self.knots[N - 1] = tail_pos.move_delta(diff);
-        self.tail_positions.insert(self.knots[N - 1]);
+        let direction = match self.undone.pop() {
+            Some(direction) => direction,
+            None => match self.directions.next()? {
+                Ok(direction) => direction,
+                Err(e) => {
+                    self.error = Some(e.to_string());
+                    self.paused = true;
+                    return None;
+                }
+            },
+        };
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        let mut snapshot = Snapshot {
+            rope: self.rope.clone(),
+            last_direction: self.last_direction,
+            new_cells: Vec::new(),
+        };
+        self.last_direction = Some(direction);
+        let tail = match &self.trace {
+            // The recorded knots, rather than moving the rope again.
+            Some(trace) => {
+                self.rope = Rope::from_knots(trace.knots_at(self.step + 1));
+                self.rope.tail()
+            }
+            None => self.rope.step(direction),
+        };
+        for (i, (trail, &knot)) in self.trails.iter_mut().zip(self.rope.knots()).enumerate() {
+            if trail.insert(knot) {
+                snapshot.new_cells.push((i, knot));
+            }
+        }
+        if tail != snapshot.rope.tail() {
+            *self.tail_visits.entry(tail).or_default() += 1;
+        }
+        self.history.push_back(snapshot);
+        self.step += 1;
         Some(())
     }
+
+    /// Undoes the last step, if still in the history.
+    fn step_back(&mut self) -> Option<()> {
+        let snapshot = self.history.pop_back()?;
+        self.undone
+            .push(self.last_direction.expect("a step was made"));
+        for (i, cell) in snapshot.new_cells {
+            self.trails[i].remove(cell);
+        }
+        let tail = self.rope.tail();
+        if tail != snapshot.rope.tail() {
+            let visits = self.tail_visits.get_mut(&tail).expect("visited");
+            *visits -= 1;
+            if *visits == 0 {
+                self.tail_visits.remove(&tail);
+            }
+        }
+        self.rope = snapshot.rope;
+        self.last_direction = snapshot.last_direction;
+        self.step -= 1;
+        Some(())
+    }
+
+    /// Goes to the given step, or to the last one if there are fewer.
+    fn jump_to(&mut self, target: usize) {
+        if self.step - target.min(self.step) > self.history.len() {
+            self.restart();
+        }
+        while self.step > target && self.step_back().is_some() {}
+        while self.step < target && self.step().is_some() {}
+    }
 }
 
-impl<const N: usize> eframe::App for MyApp<N> {
+impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
@@ -89,10 +395,24 @@ impl<const N: usize> eframe::App for MyApp<N> {
                     .unwrap()
                     .size *= 1.4;
 
+                if ui.button("Open…").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().pick_file() {
+                        self.open(&path);
+                    }
+                }
+                if ui.button("Save trace…").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().set_file_name("run.d9tr").save_file()
+                    {
+                        self.save_trace(&path);
+                    }
+                }
                 if ui.button("Reset").clicked() {
-                    *self = Self::init();
+                    self.restart();
                 }
-                if ui.button("Step").clicked() {
+                if ui.button("⏴ Step").clicked() {
+                    self.step_back();
+                }
+                if ui.button("Step ⏵").clicked() {
                     self.step();
                 }
 
@@ -100,14 +420,36 @@ impl<const N: usize> eframe::App for MyApp<N> {
                 ui.toggle_value(&mut self.paused, if paused { "▶" } else { "⏸" });
 
                 ui.toggle_value(&mut self.show_sidebar, "Sidebar");
+
+                ui.separator();
+                let mode = &mut self.camera.mode;
+                ui.selectable_value(mode, CameraMode::Free, "Free camera");
+                ui.selectable_value(mode, CameraMode::FollowHead, "Follow head");
+                ui.selectable_value(mode, CameraMode::FitVisited, "Fit visited");
             });
 
             ui.horizontal(|ui| {
                 ui.label("Speed: ");
                 ui.add(Slider::new(&mut self.speed, 1..=20).prefix("x"));
+                ui.label("Knots: ");
+                // A trace is replayed with the knots it was recorded with.
+                let knots = Slider::new(&mut self.knots, KNOTS);
+                if ui.add_enabled(self.trace.is_none(), knots).changed() {
+                    self.restart();
+                }
+                ui.label(format!("Step {}", self.step));
+                ui.add(DragValue::new(&mut self.jump_target).prefix("to "));
+                if ui.button("Jump").clicked() {
+                    self.jump_to(self.jump_target);
+                }
             });
         });
 
+        let dropped = ctx.input(|i| i.raw.dropped_files.first().and_then(|f| f.path.clone()));
+        if let Some(path) = dropped {
+            self.open(&path);
+        }
+
         if !self.paused {
             for _ in 0..self.speed {
                 self.step();
@@ -117,7 +459,16 @@ impl<const N: usize> eframe::App for MyApp<N> {
 
         if self.show_sidebar {
             egui::SidePanel::right("side_panel").show(ctx, |ui| {
-                ui.label(format!("{} places visited", self.tail_positions.len()));
+                ui.label(format!("{} places visited", self.tail_positions().len()));
+                ui.horizontal(|ui| {
+                    let overlay = &mut self.overlay;
+                    ui.selectable_value(overlay, Overlay::TailVisits, "Tail");
+                    ui.selectable_value(overlay, Overlay::KnotTrails, "Trails");
+                    ui.selectable_value(overlay, Overlay::Heatmap, "Heatmap");
+                });
+                if let Some(error) = &self.error {
+                    ui.colored_label(Color32::RED, error);
+                }
                 egui::ScrollArea::new([false, true]).show(ui, |ui| {
                     if let Some(dir) = self.last_direction {
                         let arrow = match dir {
@@ -125,6 +476,10 @@ impl<const N: usize> eframe::App for MyApp<N> {
                             Direction::Down => "⬇",
                             Direction::Right => "➡",
                             Direction::Left => "⬅",
+                            Direction::UpLeft => "⬉",
+                            Direction::UpRight => "⬈",
+                            Direction::DownLeft => "⬋",
+                            Direction::DownRight => "⬊",
                         };
                         ui.label(arrow);
                     }
@@ -137,47 +492,95 @@ impl<const N: usize> eframe::App for MyApp<N> {
                 painter_size = egui::vec2(500.0, 500.0);
             }
 
-            const SIDE: f32 = 5.0;
+            let (res, painter) = ui.allocate_painter(painter_size, Sense::drag());
+            let rect = res.rect;
 
-            let (res, painter) = ui.allocate_painter(painter_size, Sense::hover());
-            let center = res.rect.center().to_vec2();
-
-            let to_panel_pos = |pos: Position| {
-                (egui::vec2(pos.x() as f32 * SIDE, pos.y() as f32 * SIDE * -1.0) + center).to_pos2()
-            };
-
-            let half_width = (painter_size.x / SIDE).floor() as i16;
-            let half_height = (painter_size.y / SIDE).floor() as i16;
-
-            for x in -half_width..half_width {
-                for y in -half_height..half_height {
-                    let dot = Position::new(x, y);
-                    let color = if dot.x() == 0 && dot.y() == 0 {
-                        Color32::WHITE
-                    } else if self.tail_positions.contains(&dot) {
-                        Color32::DARK_RED
-                    } else {
-                        continue;
+            if res.dragged() {
+                self.camera.mode = CameraMode::Free;
+                self.camera.pan(res.drag_delta());
+            }
+            if let Some(pointer) = res.hover_pos() {
+                let scroll = ui.input(|i| i.scroll_delta.y);
+                if scroll != 0.0 {
+                    if self.camera.mode == CameraMode::FitVisited {
+                        self.camera.mode = CameraMode::Free;
+                    }
+                    self.camera.zoom_at(rect, pointer, (scroll * 0.002).exp());
+                }
+            }
+            match self.camera.mode {
+                CameraMode::Free => {}
+                CameraMode::FollowHead => self.camera.center_on(self.rope.head()),
+                CameraMode::FitVisited => {
+                    // The head goes everywhere the other knots go.
+                    let trail = match self.overlay {
+                        Overlay::KnotTrails => &self.trails[0],
+                        Overlay::TailVisits | Overlay::Heatmap => {
+                            self.trails.last().expect("a rope has a tail")
+                        }
                     };
-
-                    let dot_pos = to_panel_pos(dot);
-                    painter.circle_stroke(dot_pos, 1.0, Stroke::new(2.0, color));
+                    let cells = trail.iter();
+                    self.camera
+                        .fit(rect.size(), cells.chain(self.rope.knots().iter().copied()));
                 }
             }
 
-            // paint the head
-            let head_pos = to_panel_pos(self.knots[0]);
-            painter.circle_stroke(head_pos, 2.0, Stroke::new(2.0, Color32::GREEN));
+            let to_panel_pos = |pos: Position| self.camera.screen_pos(rect, pos);
+            // Dots keep their size relative to the cells.
+            let scale = self.camera.zoom / Camera::DEFAULT_ZOOM;
+            let (min, max) = self.camera.visible_cells(rect);
+            let in_view = |pos: &Position| {
+                (min.x()..=max.x()).contains(&pos.x()) && (min.y()..=max.y()).contains(&pos.y())
+            };
 
-            for w in self.knots[0..N - 1].windows(2) {
-                // paint the in between knot
-                let knot_pos = to_panel_pos(w[1]);
-                painter.circle_stroke(knot_pos, 2.0, Stroke::new(2.0, Color32::LIGHT_GRAY));
+            let origin = Position::default();
+            let paint_dot = |dot: Position, color: Color32| {
+                let stroke = Stroke::new(2.0 * scale, color);
+                painter.circle_stroke(to_panel_pos(dot), scale, stroke);
+            };
+            match self.overlay {
+                Overlay::TailVisits => {
+                    let visited = self.tail_positions().iter().filter(in_view);
+                    for dot in visited.filter(|&dot| dot != origin) {
+                        paint_dot(dot, Color32::DARK_RED);
+                    }
+                }
+                Overlay::KnotTrails => {
+                    // From the head, so that the trail of the tail is on top.
+                    let knots = self.trails.len();
+                    for (i, trail) in self.trails.iter().enumerate() {
+                        for dot in trail.iter().filter(in_view) {
+                            paint_dot(dot, knot_color(i, knots));
+                        }
+                    }
+                }
+                Overlay::Heatmap => {
+                    // On a log scale, a few cells being visited way more.
+                    let max = self.tail_visits.values().copied().max().unwrap_or(1);
+                    let max = (max as f32).ln_1p();
+                    for (&dot, &visits) in self.tail_visits.iter().filter(|(dot, _)| in_view(dot)) {
+                        paint_dot(dot, heat_color((visits as f32).ln_1p() / max));
+                    }
+                }
             }
+            let stroke = Stroke::new(2.0 * scale, Color32::WHITE);
+            painter.circle_stroke(to_panel_pos(origin), scale, stroke);
 
-            // paint the tail
-            let tail_pos = to_panel_pos(self.knots[1]);
-            painter.circle_stroke(tail_pos, 2.0, Stroke::new(2.0, Color32::YELLOW))
+            let knot_radius = (2.0 * scale).max(2.0);
+            // From the tail to the head, so that the head is always on top.
+            let knots = self.rope.knots();
+            for (i, &knot) in knots.iter().enumerate().rev() {
+                let color = if i == 0 {
+                    Color32::GREEN
+                } else if i == knots.len() - 1 {
+                    Color32::YELLOW
+                } else if self.overlay == Overlay::KnotTrails {
+                    knot_color(i, knots.len())
+                } else {
+                    Color32::LIGHT_GRAY
+                };
+                painter.circle_stroke(to_panel_pos(knot), knot_radius, Stroke::new(2.0, color));
+            }
         });
     }
 }
@@ -194,32 +597,171 @@ D 1
 L 5
 R 2";
 
+    const LARGER_INPUT: &str = "R 5
+U 8
+L 8
+D 3
+R 17
+D 10
+L 25
+U 20";
+
     #[test]
     fn test_part1() {
-        let reader = BufReader::new(INPUT.as_bytes());
-        let mut app = MyApp::<2>::init_from(reader);
+        let mut app = MyApp::init_from(INPUT.as_bytes(), 2);
         while app.step().is_some() {}
-        assert_eq!(app.tail_positions.len(), 13);
+        assert_eq!(app.tail_positions().len(), 13);
     }
 
     #[test]
     fn test_part2() {
-        let reader = BufReader::new(INPUT.as_bytes());
-        let mut app = MyApp::<10>::init_from(reader);
+        let mut app = MyApp::init_from(INPUT.as_bytes(), 10);
         while app.step().is_some() {}
-        assert_eq!(app.tail_positions.len(), 1);
+        assert_eq!(app.tail_positions().len(), 1);
 
-        let input = "R 5
-U 8
-L 8
-D 3
-R 17
-D 10
-L 25
-U 20";
-        let reader = BufReader::new(input.as_bytes());
-        let mut app = MyApp::<10>::init_from(reader);
+        let mut app = MyApp::init_from(LARGER_INPUT.as_bytes(), 10);
         while app.step().is_some() {}
-        assert_eq!(app.tail_positions.len(), 36);
+        assert_eq!(app.tail_positions().len(), 36);
+    }
+
+    #[test]
+    fn test_overlays() {
+        let mut app = MyApp::init_from(LARGER_INPUT.as_bytes(), 10);
+        while app.step().is_some() {}
+        assert_eq!(app.trails.len(), 10);
+        assert_eq!(app.trails[0].len(), 96);
+        assert_eq!(app.trails[1].len(), 88);
+        assert_eq!(app.tail_positions().len(), 36);
+        // This tail never comes back to a cell.
+        assert_eq!(app.tail_visits.len(), 36);
+        assert_eq!(app.tail_visits.values().sum::<u32>(), 36);
+
+        while app.step_back().is_some() {}
+        assert!(app.trails.iter().all(|trail| trail.len() == 1));
+        assert_eq!(app.tail_visits, HashMap::from([(Position::default(), 1)]));
+
+        // Every move of the tail into a cell, counting the start.
+        let mut app = MyApp::init_from(INPUT.as_bytes(), 2);
+        while app.step().is_some() {}
+        assert_eq!(app.tail_visits.len(), 13);
+        let moves = app.tail_visits.values().sum::<u32>();
+        assert!(moves > 13);
+        app.step_back();
+        while app.step().is_some() {}
+        assert_eq!(app.tail_visits.values().sum::<u32>(), moves);
+
+        assert_ne!(knot_color(0, 10), knot_color(1, 10));
+        assert_eq!(heat_color(0.0), Color32::from_rgb(30, 30, 120));
+        assert_eq!(heat_color(1.0), Color32::from_rgb(255, 230, 60));
+    }
+
+    #[test]
+    fn test_trace_replay() {
+        let mut app = MyApp::init_from(LARGER_INPUT.as_bytes(), 10);
+        let mut bytes = Vec::new();
+        app.record_trace().unwrap().write_to(&mut bytes).unwrap();
+
+        let mut replay = MyApp::init_from(Vec::new(), 2);
+        replay.load(bytes);
+        assert!(replay.error.is_none());
+        assert_eq!(replay.knots, 10);
+        while app.step().is_some() {
+            replay.step().unwrap();
+            assert_eq!(replay.rope, app.rope);
+        }
+        assert!(replay.step().is_none());
+        assert_eq!(replay.tail_positions().len(), 36);
+        assert_eq!(replay.trails[0].len(), app.trails[0].len());
+
+        replay.jump_to(10);
+        assert_eq!(
+            replay.rope.knots(),
+            replay.trace.as_ref().unwrap().knots_at(10)
+        );
+        replay.restart();
+        assert_eq!(replay.step, 0);
+        assert_eq!(replay.rope, Rope::new(10));
+
+        // Opening an input stops replaying.
+        replay.load(INPUT.as_bytes().to_vec());
+        assert!(replay.trace.is_none());
+        while replay.step().is_some() {}
+        assert_eq!(replay.tail_positions().len(), 1);
+
+        // Traces with more knots than the slider allows are not replayed.
+        let directions = get_directions(BufReader::new(INPUT.as_bytes())).map(Result::unwrap);
+        let mut bytes = Vec::new();
+        Trace::record(31, directions).write_to(&mut bytes).unwrap();
+        replay.load(bytes);
+        assert!(replay.error.is_some());
+        assert!(replay.trace.is_none() && KNOTS.contains(&replay.knots));
+    }
+
+    #[test]
+    fn test_camera() {
+        let rect = Rect::from_min_size(pos2(100.0, 50.0), vec2(400.0, 200.0));
+        let mut camera = Camera::new();
+        assert_eq!(camera.screen_pos(rect, Position::default()), rect.center());
+        assert_eq!(
+            camera.screen_pos(rect, Position::new(2, 1)),
+            pos2(310.0, 145.0)
+        );
+
+        // The cell under the pointer stays there while zooming.
+        let pointer = pos2(400.0, 100.0);
+        let cell = camera.cell_at(rect, pointer);
+        camera.zoom_at(rect, pointer, 3.0);
+        assert_eq!(camera.zoom, 15.0);
+        assert!((camera.cell_at(rect, pointer) - cell).length() < 1e-4);
+
+        // Dragging right shows what is on the left.
+        camera.center_on(Position::new(10, 10));
+        camera.pan(vec2(30.0, 15.0));
+        assert_eq!(camera.center, pos2(8.0, 11.0));
+
+        let cells = [
+            Position::new(-10, 0),
+            Position::new(10, 4),
+            Position::new(0, -4),
+        ];
+        camera.fit(rect.size(), cells.into_iter());
+        assert_eq!(camera.center, pos2(0.0, 0.0));
+        assert_eq!(camera.zoom, 400.0 / 23.0);
+        let (min, max) = camera.visible_cells(rect);
+        assert!(cells
+            .iter()
+            .all(|pos| (min.x()..=max.x()).contains(&pos.x())
+                && (min.y()..=max.y()).contains(&pos.y())));
+    }
+
+    #[test]
+    fn test_history() {
+        let mut app = MyApp::init_from(LARGER_INPUT.as_bytes(), 10);
+        app.jump_to(40);
+        let (rope, visited) = (app.rope.clone(), app.tail_positions().len());
+        app.jump_to(90);
+        assert_eq!(app.step, 90);
+        while app.step_back().is_some() {}
+        assert_eq!((app.step, app.tail_positions().len()), (0, 1));
+        assert!(app
+            .rope
+            .knots()
+            .iter()
+            .all(|knot| *knot == Position::default()));
+        app.jump_to(40);
+        assert_eq!((&app.rope, app.tail_positions().len()), (&rope, visited));
+        // Past the end of the input.
+        app.jump_to(1000);
+        assert_eq!((app.step, app.tail_positions().len()), (96, 36));
+
+        // Going back further than the history replays from the start.
+        app.history.clear();
+        app.jump_to(40);
+        assert_eq!((&app.rope, app.tail_positions().len()), (&rope, visited));
+
+        app.knots = 2;
+        app.restart();
+        app.jump_to(1000);
+        assert_eq!(app.tail_positions().len(), 88);
     }
 }
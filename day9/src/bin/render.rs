@@ -0,0 +1,74 @@
+use std::{
+    env,
+    fmt::Display,
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+    process,
+};
+
+use day9::{get_directions, Direction, GifAnimation, PngSequence, Renderer};
+
+const USAGE: &str = "usage: render <input> <output.gif | output-dir> [stride] [cell-size] [knots]";
+
+fn main() {
+    env_logger::init();
+
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let (input, output) = match &args[..] {
+        [input, output, ..] if args.len() <= 5 => (input, Path::new(output)),
+        _ => {
+            eprintln!("{USAGE}");
+            process::exit(2);
+        }
+    };
+    let number = |i: usize, default| {
+        args.get(i).map_or(default, |arg: &String| {
+            arg.parse().unwrap_or_else(|_| {
+                eprintln!("invalid number {arg:?}\n{USAGE}");
+                process::exit(2);
+            })
+        })
+    };
+    let (stride, cell_size, knots) = (number(2, 1), number(3, 4), number(4, 10));
+    if knots < 2 {
+        eprintln!("a rope needs at least 2 knots\n{USAGE}");
+        process::exit(2);
+    }
+
+    let file = File::open(input).unwrap_or_else(|e| fail(format!("cannot open {input}: {e}")));
+    let directions = get_directions(BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|e| fail(e));
+    let renderer = Renderer::fit(&directions, u32::try_from(cell_size).unwrap_or(u32::MAX))
+        .unwrap_or_else(|e| fail(e));
+
+    let frames = if output.extension().is_some_and(|ext| ext == "gif") {
+        render_gif(&renderer, knots, &directions, stride, output)
+    } else {
+        PngSequence::new(output)
+            .and_then(|mut sequence| renderer.render_run(knots, &directions, stride, &mut sequence))
+    }
+    .unwrap_or_else(|e| fail(format!("cannot write {}: {e}", output.display())));
+    println!("{frames} frames written to {}", output.display());
+}
+
+fn render_gif(
+    renderer: &Renderer,
+    knots: usize,
+    directions: &[Direction],
+    stride: usize,
+    output: &Path,
+) -> io::Result<usize> {
+    let writer = BufWriter::new(File::create(output)?);
+    let mut gif = GifAnimation::new(writer, renderer.width(), renderer.height(), 4)?;
+    let frames = renderer.render_run(knots, directions, stride, &mut gif)?;
+    gif.finish()?.flush()?;
+    Ok(frames)
+}
+
+// Reports a failure the same way as every other one of this tool.
+fn fail(error: impl Display) -> ! {
+    eprintln!("{error}");
+    process::exit(1);
+}
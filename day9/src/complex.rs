@@ -1,16 +1,19 @@
-use std::{collections::HashSet, io::BufRead};
+use std::io::{self, BufRead};
 
+use itertools::Itertools;
 use log::debug;
 use nom::{
-    character::complete::{one_of, space1, u8},
-    combinator::map,
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{char, line_ending, not_line_ending, one_of, space0, space1, u32},
+    combinator::{cut, eof, map, opt, value},
     error::{convert_error, VerboseError},
-    sequence::separated_pair,
+    sequence::{pair, preceded, terminated, tuple},
     Err, IResult,
 };
-use smallvec::{Array, SmallVec};
+use thiserror::Error;
 
-use crate::{Direction, Position};
+use crate::{Direction, Position, Rope, VisitedCells};
 
 #[cfg(feature = "dhat-heap")]
 #[global_allocator]
@@ -19,115 +22,199 @@ static GLOBAL: dhat::Alloc = dhat::Alloc;
 #[inline(always)]
 fn parse_direction(input: &str) -> IResult<&str, Direction, VerboseError<&str>> {
     use Direction::*;
-    map(one_of("LRUD"), |c| match c {
-        'L' => Left,
-        'R' => Right,
-        'U' => Up,
-        'D' => Down,
-        _ => unreachable!(),
-    })(input)
+    alt((
+        value(UpLeft, tag("UL")),
+        value(UpRight, tag("UR")),
+        value(DownLeft, tag("DL")),
+        value(DownRight, tag("DR")),
+        map(one_of("LRUD"), |c| match c {
+            'L' => Left,
+            'R' => Right,
+            'U' => Up,
+            'D' => Down,
+            _ => unreachable!(),
+        }),
+    ))(input)
 }
 
+// Once the direction is read, the errors point at the rest of the command,
+// which may only be followed by a comment.
 #[inline(always)]
-fn parse_command(input: &str) -> IResult<&str, (Direction, u8), VerboseError<&str>> {
-    separated_pair(parse_direction, space1, u8)(input)
+fn parse_command(input: &str) -> IResult<&str, (Direction, u32), VerboseError<&str>> {
+    pair(
+        parse_direction,
+        cut(preceded(space1, terminated(u32, parse_comment))),
+    )(input)
+}
+
+// The end of a line, with an optional `#` comment.
+#[inline(always)]
+fn parse_comment(input: &str) -> IResult<&str, (), VerboseError<&str>> {
+    value(
+        (),
+        tuple((
+            space0,
+            opt(pair(char('#'), not_line_ending)),
+            alt((line_ending, eof)),
+        )),
+    )(input)
+}
+
+#[inline(always)]
+fn parse_line(input: &str) -> IResult<&str, Option<(Direction, u32)>, VerboseError<&str>> {
+    alt((map(parse_command, Some), value(None, parse_comment)))(input)
+}
+
+#[derive(Error, Debug)]
+pub enum DirectionParseError {
+    #[error("failed to read line {line}")]
+    Io {
+        line: usize,
+        #[source]
+        source: io::Error,
+    },
+    /// `verbose` is nom's error trace, pointing at the faulty input.
+    #[error("invalid command at line {line}:\n{verbose}")]
+    Invalid { line: usize, verbose: String },
+    #[error("command at line {line} moves the head out of the range of positions")]
+    OutOfRange { line: usize },
+}
+
+/// Parses the `line`-th line of input, `None` being a blank or comment line,
+/// and moves `head` where the command takes it.
+pub(crate) fn parse_command_line(
+    input: &str,
+    line: usize,
+    head: &mut Position,
+) -> Result<Option<(Direction, u32)>, DirectionParseError> {
+    let verbose = match parse_line(input) {
+        Ok((rest, parsed)) => {
+            debug!("rest: {rest:?}, parsed: {parsed:?}");
+            if let Some((direction, count)) = parsed {
+                *head = head
+                    .checked_move_to(direction, count)
+                    .ok_or(DirectionParseError::OutOfRange { line })?;
+            }
+            return Ok(parsed);
+        }
+        Err(Err::Incomplete(_)) => "incomplete command".to_string(),
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => convert_error(input, e),
+    };
+    Err(DirectionParseError::Invalid { line, verbose })
 }
 
 #[derive(Clone)]
-pub struct DirectionReaderIterator<B: BufRead, const N: usize>
-where
-    [Direction; N]: Array<Item = Direction>,
-{
+pub struct DirectionReaderIterator<B: BufRead> {
     inner: B,
     buffer: String,
-    res_stack: SmallVec<[Direction; N]>,
+    // The direction being repeated and how many times it still is.
+    pending: Option<(Direction, u32)>,
+    line: usize,
+    // Where the head is once the pending moves are done.
+    head: Position,
     finished: bool,
 }
 
-impl<B: BufRead, const N: usize> DirectionReaderIterator<B, N>
-where
-    [Direction; N]: Array<Item = Direction>,
-{
+impl<B: BufRead> DirectionReaderIterator<B> {
     #[inline(always)]
     pub fn new(reader: B) -> Self {
         Self {
             inner: reader,
             buffer: String::new(),
-            res_stack: SmallVec::new(),
+            pending: None,
+            line: 0,
+            head: Position::default(),
             finished: false,
         }
     }
 }
 
-impl<B: BufRead, const N: usize> Iterator for DirectionReaderIterator<B, N>
-where
-    [Direction; N]: Array<Item = Direction>,
-{
-    type Item = Direction;
+impl<B: BufRead> Iterator for DirectionReaderIterator<B> {
+    type Item = Result<Direction, DirectionParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         debug!("next");
         loop {
-            if !self.res_stack.is_empty() || self.finished {
-                return self.res_stack.pop();
+            if let Some((direction, remaining)) = self.pending.as_mut() {
+                let direction = *direction;
+                *remaining -= 1;
+                if *remaining == 0 {
+                    self.pending = None;
+                }
+                return Some(Ok(direction));
+            }
+            if self.finished {
+                return None;
             }
 
-            let mut bread = self.inner.read_line(&mut self.buffer).unwrap();
-            debug!("buffer: {:?}, read: {:?}", self.buffer, bread);
-            // Retry to be sure we reach the eof.
-            if bread == 0 {
-                debug!("retry");
-                bread = self.inner.read_line(&mut self.buffer).unwrap();
-                debug!("buffer: {:?}, read: {:?}", self.buffer, bread);
-                // End of iterator.
-                if bread == 0 {
+            match self.read_line() {
+                Ok(0) => {
+                    // End of iterator.
                     self.finished = true;
                     continue;
                 }
+                Ok(_) => self.line += 1,
+                Err(source) => {
+                    self.finished = true;
+                    let line = self.line + 1;
+                    return Some(Err(DirectionParseError::Io { line, source }));
+                }
             }
 
-            match parse_command(self.buffer.as_str()) {
-                Ok((rest, parsed)) => {
-                    debug!("rest: {rest:?}, parsed: {parsed:?}");
-                    let iter = std::iter::repeat(parsed.0).take(parsed.1.into());
-                    self.res_stack.extend(iter);
+            match parse_command_line(self.buffer.as_str(), self.line, &mut self.head) {
+                Ok(parsed) => {
+                    self.pending = parsed.filter(|&(_, count)| count > 0);
                     self.buffer.clear();
-                    continue;
-                }
-                Err(Err::Incomplete(_)) => {
-                    panic!()
                 }
-                Err(Err::Error(e)) | Err(Err::Failure(e)) => {
-                    println!("parse error {}", convert_error(self.buffer.as_str(), e));
-                    panic!()
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
                 }
-            };
+            }
         }
     }
 }
 
+impl<B: BufRead> DirectionReaderIterator<B> {
+    fn read_line(&mut self) -> io::Result<usize> {
+        let mut bread = self.inner.read_line(&mut self.buffer)?;
+        debug!("buffer: {:?}, read: {:?}", self.buffer, bread);
+        // Retry to be sure we reach the eof.
+        if bread == 0 {
+            debug!("retry");
+            bread = self.inner.read_line(&mut self.buffer)?;
+            debug!("buffer: {:?}, read: {:?}", self.buffer, bread);
+        }
+        Ok(bread)
+    }
+}
+
 #[inline(always)]
-pub fn get_directions(input: impl BufRead) -> impl Iterator<Item = Direction> {
-    DirectionReaderIterator::<_, 20>::new(input)
+pub fn get_directions(
+    input: impl BufRead,
+) -> impl Iterator<Item = Result<Direction, DirectionParseError>> {
+    DirectionReaderIterator::new(input)
 }
 
-pub fn solve_part1_complex(input: impl BufRead) -> usize {
-    let iter = get_directions(input);
-    let (_, tail_positions) = iter.fold(
-        (
-            (Position { x: 0, y: 0 }, Position { x: 0, y: 0 }),
-            HashSet::with_capacity(5900),
-        ),
-        |((last_head_pos, last_tail_pos), mut tail_positions), direction| {
+pub fn solve_part1_complex(input: impl BufRead) -> Result<usize, DirectionParseError> {
+    get_directions(input).process_results(|directions| tail_positions_count(directions))
+}
+
+pub(crate) fn tail_positions_count(iter: impl Iterator<Item = Direction>) -> usize {
+    // The tail starts at the origin.
+    let mut visited = VisitedCells::from_iter([Position { x: 0, y: 0 }]);
+    iter.fold(
+        (Position { x: 0, y: 0 }, Position { x: 0, y: 0 }),
+        |(last_head_pos, last_tail_pos), direction| {
             let new_head_pos = last_head_pos.move_to(direction);
-            let new_tail_pos = if !new_head_pos.is_adjacent(last_tail_pos) {
-                tail_positions.insert(last_head_pos);
-                last_head_pos
-            } else {
-                last_tail_pos
-            };
-            ((new_head_pos, new_tail_pos), tail_positions)
+            let new_tail_pos = last_tail_pos.move_delta(new_head_pos - last_tail_pos);
+            visited.insert(new_tail_pos);
+            (new_head_pos, new_tail_pos)
         },
     );
-    tail_positions.len()
+    visited.len()
+}
+
+pub fn solve_part2_complex(input: impl BufRead) -> Result<usize, DirectionParseError> {
+    get_directions(input).process_results(|directions| Rope::count_tail_positions(10, directions))
 }
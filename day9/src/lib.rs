@@ -7,13 +7,25 @@ use itertools::Itertools;
 
 pub mod complex;
 pub use complex::*;
+pub mod render;
+pub use render::{Bounds, Frame, FrameSink, GifAnimation, PngSequence, Renderer};
+pub mod rope;
+pub use rope::Rope;
+pub mod trace;
+pub use trace::{Trace, TraceError, TRACE_MAGIC};
+pub mod visited;
+pub use visited::VisitedCells;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Direction {
     Left,
     Right,
     Up,
     Down,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default, Debug)]
@@ -67,44 +79,56 @@ impl Position {
                 x: self.x,
                 y: self.y - 1,
             },
+            UpLeft => Position {
+                x: self.x - 1,
+                y: self.y + 1,
+            },
+            UpRight => Position {
+                x: self.x + 1,
+                y: self.y + 1,
+            },
+            DownLeft => Position {
+                x: self.x - 1,
+                y: self.y - 1,
+            },
+            DownRight => Position {
+                x: self.x + 1,
+                y: self.y - 1,
+            },
         }
     }
 
-    pub fn move_delta(self, delta: Position) -> Self {
-        let (dx, dy) = match (delta.x, delta.y) {
-            // overlapping
-            (0, 0) => (0, 0),
-            // touching up/left/down/right
-            (0, 1) | (1, 0) | (0, -1) | (-1, 0) => (0, 0),
-            // touching diagonally
-            (1, 1) | (1, -1) | (-1, 1) | (-1, -1) => (0, 0),
-            // need to move up/left/down/right
-            (0, 2) => (0, 1),
-            (0, -2) => (0, -1),
-            (2, 0) => (1, 0),
-            (-2, 0) => (-1, 0),
-            // need to move to the right diagonally
-            (2, 1) => (1, 1),
-            (2, -1) => (1, -1),
-            // need to move to the left diagonally
-            (-2, 1) => (-1, 1),
-            (-2, -1) => (-1, -1),
-            // need to move up/down diagonally
-            (1, 2) => (1, 1),
-            (-1, 2) => (-1, 1),
-            (1, -2) => (1, -1),
-            (-1, -2) => (-1, -1),
-            // 🆕 need to move diagonally
-            (-2, -2) => (-1, -1),
-            (-2, 2) => (-1, 1),
-            (2, -2) => (1, -1),
-            (2, 2) => (1, 1),
-            _ => panic!("unhandled case: {delta:?}"),
+    /// Moves `steps` times toward `direction`, or returns `None` if that
+    /// goes out of the range of positions.
+    pub fn checked_move_to(self, direction: Direction, steps: u32) -> Option<Self> {
+        let delta = Position::default().move_to(direction);
+        let coord = |from: i16, delta: i16| {
+            i16::try_from(i64::from(from) + i64::from(delta) * i64::from(steps)).ok()
         };
-        Self {
-            x: self.x + dx,
-            y: self.y + dy,
+        Some(Position::new(
+            coord(self.x, delta.x)?,
+            coord(self.y, delta.y)?,
+        ))
+    }
+
+    /// Moves toward a knot at `delta` from this one until touching it, one
+    /// step at a time, horizontally, vertically or diagonally.
+    #[inline(always)]
+    pub fn move_delta(self, delta: Position) -> Self {
+        self.move_delta_visiting(delta, |_| {})
+    }
+
+    /// Same as [`Position::move_delta`], calling `visit` with every cell
+    /// stepped on, the last one being the returned position.
+    pub fn move_delta_visiting(self, delta: Position, mut visit: impl FnMut(Position)) -> Self {
+        let target = self + delta;
+        let mut pos = self;
+        while !pos.is_adjacent(target) {
+            let diff = target - pos;
+            pos += Position::new(diff.x.signum(), diff.y.signum());
+            visit(pos);
         }
+        pos
     }
 }
 
@@ -137,33 +161,25 @@ impl Sub for Position {
     }
 }
 
-pub fn solve_part1(lines: Lines) -> usize {
-    let (_, tail_positions) = lines
-        .flat_map(|line| {
-            let splitted = line.split_ascii_whitespace().collect::<Vec<_>>();
-            let steps = splitted[1].parse().expect("safe");
-            use Direction::*;
-            let direction = match splitted[0] {
-                "L" => Left,
-                "R" => Right,
-                "U" => Up,
-                "D" => Down,
-                _ => unreachable!(),
-            };
-            std::iter::repeat(direction).take(steps)
-        })
-        .fold(
-            (Position { x: 0, y: 0 }, vec![Position { x: 0, y: 0 }]),
-            |(last_head_pos, mut tail_positions), direction| {
-                let new_head_pos = last_head_pos.move_to(direction);
-                let last_tail_pos = tail_positions.last().copied().expect("safe");
-                if !new_head_pos.is_adjacent(last_tail_pos) {
-                    tail_positions.push(last_head_pos);
-                }
-                (new_head_pos, tail_positions)
-            },
-        );
-    tail_positions.iter().unique().count()
+// Blank lines and lines starting with `#` are skipped.
+fn parse_directions(
+    lines: Lines<'_>,
+) -> impl Iterator<Item = Result<Direction, DirectionParseError>> + '_ {
+    let mut head = Position::default();
+    lines
+        .zip(1..)
+        .map(move |(line, n)| parse_command_line(line, n, &mut head))
+        .flatten_ok()
+        .map_ok(|(direction, steps)| std::iter::repeat_n(direction, steps as usize))
+        .flatten_ok()
+}
+
+pub fn solve_part1(lines: Lines) -> Result<usize, DirectionParseError> {
+    parse_directions(lines).process_results(|directions| tail_positions_count(directions))
+}
+
+pub fn solve_part2(lines: Lines) -> Result<usize, DirectionParseError> {
+    parse_directions(lines).process_results(|directions| Rope::count_tail_positions(10, directions))
 }
 
 #[cfg(test)]
@@ -181,9 +197,18 @@ D 1
 L 5
 R 2";
 
+    const LARGER_INPUT: &str = "R 5
+U 8
+L 8
+D 3
+R 17
+D 10
+L 25
+U 20";
+
     #[test]
     fn test_part1_simple() {
-        assert_eq!(solve_part1(INPUT.lines()), 13);
+        assert_eq!(solve_part1(INPUT.lines()).unwrap(), 13);
     }
 
     #[test]
@@ -193,6 +218,297 @@ R 2";
         env_logger::init();
 
         let reader = BufReader::new(INPUT.as_bytes());
-        assert_eq!(solve_part1_complex(reader), 13);
+        assert_eq!(solve_part1_complex(reader).unwrap(), 13);
+    }
+
+    #[test]
+    fn test_part2() {
+        assert_eq!(solve_part2(INPUT.lines()).unwrap(), 1);
+        assert_eq!(solve_part2(LARGER_INPUT.lines()).unwrap(), 36);
+        let reader = BufReader::new(LARGER_INPUT.as_bytes());
+        assert_eq!(solve_part2_complex(reader).unwrap(), 36);
+    }
+
+    #[test]
+    fn test_extended_grammar() {
+        let input = "# A comment, then a blank line.
+
+UR 3
+  # Indented comment.
+DL 300
+L 0
+R 1000";
+        // Moving diagonally back and forth, then along a line.
+        assert_eq!(solve_part1(input.lines()).unwrap(), 298 + 1000 - 1);
+        let reader = BufReader::new(input.as_bytes());
+        assert_eq!(solve_part1_complex(reader).unwrap(), 298 + 1000 - 1);
+        let reader = BufReader::new(input.as_bytes());
+        let directions = get_directions(reader)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(directions.len(), 1303);
+        assert_eq!(
+            directions[..4],
+            [
+                Direction::UpRight,
+                Direction::UpRight,
+                Direction::UpRight,
+                Direction::DownLeft
+            ]
+        );
+        assert_eq!(directions[1302], Direction::Right);
+        let reader = BufReader::new(input.as_bytes());
+        assert_eq!(
+            solve_part2_complex(reader).unwrap(),
+            solve_part2(input.lines()).unwrap()
+        );
+
+        let mut rope = Rope::new(3);
+        rope.step(Direction::UpRight);
+        rope.step(Direction::UpRight);
+        rope.step(Direction::DownRight);
+        assert_eq!(
+            rope.knots(),
+            &[
+                Position::new(3, 1),
+                Position::new(2, 1),
+                Position::new(1, 1)
+            ]
+        );
+        let reader = BufReader::new("U 4294967296".as_bytes());
+        assert!(get_directions(reader).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let reader = BufReader::new("R 4\nU 4\nX 3\nD 1".as_bytes());
+        let mut directions = get_directions(reader);
+        assert_eq!(directions.by_ref().take(8).filter(Result::is_ok).count(), 8);
+        let Some(Err(DirectionParseError::Invalid { line, verbose })) = directions.next() else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(line, 3);
+        assert!(verbose.contains("X 3"), "{verbose}");
+        assert!(directions.next().is_none());
+
+        let reader = BufReader::new("R 4\nU -3".as_bytes());
+        let err = solve_part2_complex(reader).unwrap_err();
+        assert!(err.to_string().starts_with("invalid command at line 2:"));
+
+        // The line-based solvers report the same errors.
+        let err = solve_part1("R 4\n\nX 3".lines()).unwrap_err();
+        assert!(err.to_string().starts_with("invalid command at line 3:"));
+        assert!(solve_part2("R".lines()).is_err());
+        assert!(solve_part1("U 4294967296".lines()).is_err());
+    }
+
+    #[test]
+    fn test_trailing_input() {
+        let reader = BufReader::new("R 4 # Comments may follow.\nU 2#".as_bytes());
+        assert_eq!(solve_part1_complex(reader).unwrap(), 5);
+        // The error points right after the step count.
+        let Err(DirectionParseError::Invalid { line, verbose }) = solve_part1("R 4x".lines())
+        else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(line, 1);
+        assert!(verbose.contains("R 4x\n   ^"), "{verbose}");
+        let reader = BufReader::new("U 1\nR 4 junk\n".as_bytes());
+        let Err(DirectionParseError::Invalid { line, verbose }) = solve_part2_complex(reader)
+        else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(line, 2);
+        assert!(verbose.contains("R 4 junk\n    ^"), "{verbose}");
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        // The head may go to the ends of the coordinates, but not past them.
+        assert_eq!(solve_part1("L 32768".lines()).unwrap(), 32768);
+        let input = "R 20000\nL 10000\nR 22767\nUR 1";
+        let Err(DirectionParseError::OutOfRange { line }) = solve_part2(input.lines()) else {
+            panic!("expected an out of range error");
+        };
+        assert_eq!(line, 4);
+        for input in ["R 40000", "# Comment.\nU 33000"] {
+            let err = solve_part1(input.lines()).unwrap_err();
+            assert!(err.to_string().starts_with("command at line"), "{err}");
+            let reader = BufReader::new(input.as_bytes());
+            assert!(solve_part1_complex(reader).is_err());
+        }
+        let reader = BufReader::new("D 20000\nD 20000".as_bytes());
+        assert_eq!(get_directions(reader).filter(Result::is_ok).count(), 20000);
+        assert!(Renderer::fit(&[Direction::Right; 40000], 1).is_err());
+    }
+
+    #[test]
+    fn test_move_delta() {
+        let start = Position::new(3, -2);
+        for dx in -8..=8 {
+            for dy in -8..=8 {
+                let delta = Position::new(dx, dy);
+                let mut visited = Vec::new();
+                let end = start.move_delta_visiting(delta, |pos| visited.push(pos));
+                assert_eq!(start.move_delta(delta), end);
+                assert!(end.is_adjacent(start + delta), "{delta:?}");
+                // One step per cell between the knots.
+                let distance = dx.abs().max(dy.abs());
+                assert_eq!(visited.len() as i16, (distance - 1).max(0), "{delta:?}");
+                assert_eq!(visited.last().copied().unwrap_or(start), end);
+                let mut previous = start;
+                for pos in visited {
+                    assert!(pos.is_adjacent(previous) && pos != previous, "{delta:?}");
+                    previous = pos;
+                }
+            }
+        }
+        // Touching knots don't move, the others follow diagonally if needed.
+        let origin = Position::default();
+        assert_eq!(origin.move_delta(Position::new(1, -1)), origin);
+        assert_eq!(origin.move_delta(Position::new(0, 2)), Position::new(0, 1));
+        assert_eq!(
+            origin.move_delta(Position::new(-2, 1)),
+            Position::new(-1, 1)
+        );
+        assert_eq!(origin.move_delta(Position::new(2, 2)), Position::new(1, 1));
+        assert_eq!(origin.move_delta(Position::new(5, 1)), Position::new(4, 1));
+    }
+
+    #[test]
+    fn test_render() {
+        struct Frames(Vec<Frame>);
+        impl FrameSink for Frames {
+            fn write_frame(&mut self, frame: &Frame) -> std::io::Result<()> {
+                self.0.push(Frame::clone(frame));
+                Ok(())
+            }
+        }
+
+        let directions = parse_directions(INPUT.lines())
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        let renderer = Renderer::fit(&directions, 2).unwrap();
+        // The head goes from (0, 0) up to (5, 4).
+        assert_eq!((renderer.width(), renderer.height()), (12, 10));
+        let mut frames = Frames(Vec::new());
+        assert_eq!(
+            renderer
+                .render_run(2, &directions, 10, &mut frames)
+                .unwrap(),
+            4
+        );
+        assert_eq!(frames.0.len(), 4);
+        // At the end the head is at (2, 2) and the tail at (1, 2).
+        let last = frames.0.last().unwrap();
+        assert_eq!(last.pixel(4, 4), [0, 255, 0]);
+        assert_eq!(last.pixel(3, 5), [255, 255, 0]);
+        assert_eq!(last.pixel(1, 9), [255, 255, 255]);
+        assert_eq!(last.pixel(2, 8), [139, 0, 0]);
+        assert_eq!(last.pixel(0, 0), [27, 27, 27]);
+        // The first frame only shows the rope at the origin.
+        assert_eq!(frames.0[0].pixel(0, 8), [0, 255, 0]);
+        assert_eq!(frames.0[0].pixel(2, 8), [27, 27, 27]);
+
+        let mut png = Vec::new();
+        last.write_png(&mut png).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        let mut gif = GifAnimation::new(Vec::new(), 12, 10, 4).unwrap();
+        renderer.render_run(2, &directions, 1, &mut gif).unwrap();
+        assert!(gif.finish().unwrap().starts_with(b"GIF89a"));
+
+        // Frames wider than 2^32 - 1 pixels can't be rendered.
+        let everywhere = Bounds {
+            min: Position::new(i16::MIN, i16::MIN),
+            max: Position::new(i16::MAX, i16::MAX),
+        };
+        assert!(Renderer::new(everywhere, 1 << 16).is_err());
+        assert!(Renderer::new(everywhere, u32::MAX).is_err());
+        let line = Bounds {
+            min: Position::new(i16::MIN, 0),
+            max: Position::new(i16::MAX, 0),
+        };
+        assert_eq!(Renderer::new(line, 2).unwrap().width(), 1 << 17);
+    }
+
+    #[test]
+    fn test_rope() {
+        assert_eq!(
+            Rope::count_tail_positions(2, parse_directions(INPUT.lines()).map(Result::unwrap)),
+            13
+        );
+        let mut rope = Rope::new(3);
+        for direction in [
+            Direction::Right,
+            Direction::Right,
+            Direction::Up,
+            Direction::Up,
+        ] {
+            rope.step(direction);
+        }
+        assert_eq!(
+            rope.knots(),
+            &[
+                Position::new(2, 2),
+                Position::new(2, 1),
+                Position::new(1, 1)
+            ]
+        );
+        // The tail only follows once the knot before it is two cells away.
+        assert_eq!(rope.step(Direction::Up), Position::new(1, 1));
+        assert_eq!(rope.step(Direction::Up), Position::new(2, 2));
+    }
+
+    #[test]
+    fn test_trace() {
+        let directions = parse_directions(LARGER_INPUT.lines())
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        let trace = Trace::record(10, directions.iter().copied());
+        assert_eq!(trace.len(), directions.len());
+        assert_eq!(trace.directions(), &directions[..]);
+        assert_eq!(trace.replay().count(), directions.len() + 1);
+        assert_eq!(trace.count_tail_positions(), 36);
+
+        let mut bytes = Vec::new();
+        trace.write_to(&mut bytes).unwrap();
+        assert!(Trace::is_trace(&bytes));
+        // A header then 5 bytes per step.
+        assert_eq!(bytes.len(), 11 + 5 * directions.len());
+        let replayed = Trace::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(replayed, trace);
+        let mut rope = Rope::new(10);
+        for (step, &direction) in (1..).zip(&directions) {
+            rope.step(direction);
+            assert_eq!(replayed.knots_at(step), rope.knots());
+        }
+
+        // An odd number of knots leaves half a byte unused.
+        let trace = Trace::record(3, parse_directions(INPUT.lines()).map(Result::unwrap));
+        let mut bytes = Vec::new();
+        trace.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 11 + 2 * trace.len());
+        assert_eq!(Trace::read_from(bytes.as_slice()).unwrap(), trace);
+
+        assert!(matches!(
+            Trace::read_from("R 4\nU 4\n".as_bytes()),
+            Err(TraceError::NotATrace)
+        ));
+        let mut invalid = bytes.clone();
+        invalid[11] = 0x0f;
+        assert!(matches!(
+            Trace::read_from(invalid.as_slice()),
+            Err(TraceError::InvalidDirection { step: 1 })
+        ));
+        invalid[11] = 0xf0;
+        assert!(matches!(
+            Trace::read_from(invalid.as_slice()),
+            Err(TraceError::InvalidMove { step: 1, knot: 1 })
+        ));
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(matches!(
+            Trace::read_from(truncated),
+            Err(TraceError::Io(_))
+        ));
     }
 }
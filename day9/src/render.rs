@@ -0,0 +1,271 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use crate::{Direction, Position, Rope, VisitedCells};
+
+/// Colors of the frames, as RGB triplets indexed by [`Color`].
+const PALETTE: [u8; 18] = [
+    27, 27, 27, // background
+    139, 0, 0, // visited
+    255, 255, 255, // origin
+    160, 160, 160, // knot
+    0, 255, 0, // head
+    255, 255, 0, // tail
+];
+
+#[derive(Clone, Copy)]
+#[repr(u8)]
+enum Color {
+    Background,
+    Visited,
+    Origin,
+    Knot,
+    Head,
+    Tail,
+}
+
+/// The rectangle of cells shown in the frames, ends included.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Bounds {
+    pub min: Position,
+    pub max: Position,
+}
+
+impl Bounds {
+    fn of(pos: Position) -> Self {
+        Self { min: pos, max: pos }
+    }
+
+    fn include(&mut self, pos: Position) {
+        self.min = Position::new(self.min.x().min(pos.x()), self.min.y().min(pos.y()));
+        self.max = Position::new(self.max.x().max(pos.x()), self.max.y().max(pos.y()));
+    }
+
+    pub fn width(&self) -> u32 {
+        (i32::from(self.max.x()) - i32::from(self.min.x()) + 1) as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        (i32::from(self.max.y()) - i32::from(self.min.y()) + 1) as u32
+    }
+}
+
+/// An image using the colors of the palette, one byte per pixel.
+#[derive(Clone)]
+pub struct Frame {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The RGB color of a pixel, `y` going down.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        let color = usize::from(self.pixels[y as usize * self.width as usize + x as usize]) * 3;
+        [PALETTE[color], PALETTE[color + 1], PALETTE[color + 2]]
+    }
+
+    pub fn write_png(&self, writer: impl Write) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(&PALETTE[..]);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(writer.finish()?)
+    }
+}
+
+/// Where the rendered frames go.
+pub trait FrameSink {
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()>;
+}
+
+/// Writes every frame to its own numbered PNG file in a directory.
+pub struct PngSequence {
+    dir: PathBuf,
+    written: usize,
+}
+
+impl PngSequence {
+    /// Creates `dir` if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, written: 0 })
+    }
+}
+
+impl FrameSink for PngSequence {
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        let path = self.dir.join(format!("frame_{:06}.png", self.written));
+        let mut writer = BufWriter::new(File::create(path)?);
+        frame.write_png(&mut writer)?;
+        writer.flush()?;
+        self.written += 1;
+        Ok(())
+    }
+}
+
+/// Writes the frames as a looping animated GIF.
+pub struct GifAnimation<W: Write> {
+    encoder: gif::Encoder<W>,
+    delay: u16,
+}
+
+impl<W: Write> GifAnimation<W> {
+    /// `delay` is the time each frame is shown, in hundredths of a second.
+    pub fn new(writer: W, width: u32, height: u32, delay: u16) -> io::Result<Self> {
+        let too_large = |_| io::Error::other("frames are too large for a GIF");
+        let width = u16::try_from(width).map_err(too_large)?;
+        let height = u16::try_from(height).map_err(too_large)?;
+        let mut encoder =
+            gif::Encoder::new(writer, width, height, &PALETTE).map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+        Ok(Self { encoder, delay })
+    }
+
+    /// Writes the end of the animation and returns the writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.encoder.into_inner()
+    }
+}
+
+impl<W: Write> FrameSink for GifAnimation<W> {
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        let frame = gif::Frame {
+            width: frame.width as u16,
+            height: frame.height as u16,
+            delay: self.delay,
+            buffer: frame.pixels.as_slice().into(),
+            ..Default::default()
+        };
+        self.encoder.write_frame(&frame).map_err(io::Error::other)
+    }
+}
+
+/// Draws a rope and the cells visited by its tail without any display,
+/// each cell being a square of `cell_size` pixels.
+#[derive(Clone, Copy, Debug)]
+pub struct Renderer {
+    bounds: Bounds,
+    cell_size: u32,
+}
+
+impl Renderer {
+    /// Fails if the frames would have more pixels than can be addressed.
+    pub fn new(bounds: Bounds, cell_size: u32) -> io::Result<Self> {
+        let cell_size = cell_size.max(1);
+        let pixels = || {
+            let width = bounds.width().checked_mul(cell_size)?;
+            let height = bounds.height().checked_mul(cell_size)?;
+            usize::try_from(width)
+                .ok()?
+                .checked_mul(usize::try_from(height).ok()?)
+        };
+        if pixels().is_none() {
+            return Err(io::Error::other("frames are too large to render"));
+        }
+        Ok(Self { bounds, cell_size })
+    }
+
+    /// A renderer showing every position a rope goes through when
+    /// following `directions`.
+    pub fn fit(directions: &[Direction], cell_size: u32) -> io::Result<Self> {
+        // The other knots never leave the area covered by the head.
+        let mut head = Position::default();
+        let mut bounds = Bounds::of(head);
+        for &direction in directions {
+            head = head
+                .checked_move_to(direction, 1)
+                .ok_or_else(|| io::Error::other("the head leaves the range of positions"))?;
+            bounds.include(head);
+        }
+        Self::new(bounds, cell_size)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.bounds.width() * self.cell_size
+    }
+
+    pub fn height(&self) -> u32 {
+        self.bounds.height() * self.cell_size
+    }
+
+    pub fn render(&self, knots: &[Position], visited: &VisitedCells) -> Frame {
+        let mut frame = Frame {
+            width: self.width(),
+            height: self.height(),
+            // Can't overflow, checked by `new`.
+            pixels: vec![Color::Background as u8; self.width() as usize * self.height() as usize],
+        };
+        for pos in visited.iter() {
+            self.fill(&mut frame, pos, Color::Visited);
+        }
+        self.fill(&mut frame, Position::default(), Color::Origin);
+        // From the tail to the head, so that the head is always on top.
+        for (i, &knot) in knots.iter().enumerate().rev() {
+            let color = match i {
+                0 => Color::Head,
+                i if i == knots.len() - 1 => Color::Tail,
+                _ => Color::Knot,
+            };
+            self.fill(&mut frame, knot, color);
+        }
+        frame
+    }
+
+    // Paints a cell, if in the bounds.
+    fn fill(&self, frame: &mut Frame, pos: Position, color: Color) {
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        if !(min.x()..=max.x()).contains(&pos.x()) || !(min.y()..=max.y()).contains(&pos.y()) {
+            return;
+        }
+        let cell_size = self.cell_size as usize;
+        let x = (i32::from(pos.x()) - i32::from(min.x())) as usize * cell_size;
+        // Rows go down while y goes up.
+        let y = (i32::from(max.y()) - i32::from(pos.y())) as usize * cell_size;
+        for row in y..y + cell_size {
+            let start = row * frame.width as usize + x;
+            frame.pixels[start..start + cell_size].fill(color as u8);
+        }
+    }
+
+    /// Moves a rope of `knots` knots along `directions`, rendering the first
+    /// state then one every `stride` steps and the last one. Returns the
+    /// number of frames written.
+    pub fn render_run(
+        &self,
+        knots: usize,
+        directions: &[Direction],
+        stride: usize,
+        sink: &mut impl FrameSink,
+    ) -> io::Result<usize> {
+        let stride = stride.max(1);
+        let mut rope = Rope::new(knots);
+        let mut visited = VisitedCells::from_iter([rope.tail()]);
+        sink.write_frame(&self.render(rope.knots(), &visited))?;
+        let mut frames = 1;
+        for (step, &direction) in (1usize..).zip(directions) {
+            visited.insert(rope.step(direction));
+            if step.is_multiple_of(stride) || step == directions.len() {
+                sink.write_frame(&self.render(rope.knots(), &visited))?;
+                frames += 1;
+            }
+        }
+        Ok(frames)
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{Direction, Position, VisitedCells};
+
+/// A rope made of knots, the head being the first one and the tail the last.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Rope {
+    knots: Box<[Position]>,
+}
+
+impl Rope {
+    /// A rope of `len` knots, all starting at the origin.
+    pub fn new(len: usize) -> Self {
+        assert!(len > 1, "a rope needs at least a head and a tail");
+        Self {
+            knots: vec![Position::default(); len].into_boxed_slice(),
+        }
+    }
+
+    /// A rope with its knots at the given positions, the head first.
+    pub fn from_knots(knots: &[Position]) -> Self {
+        assert!(knots.len() > 1, "a rope needs at least a head and a tail");
+        Self {
+            knots: knots.into(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn knots(&self) -> &[Position] {
+        &self.knots
+    }
+
+    #[inline(always)]
+    pub fn head(&self) -> Position {
+        self.knots[0]
+    }
+
+    #[inline(always)]
+    pub fn tail(&self) -> Position {
+        self.knots[self.knots.len() - 1]
+    }
+
+    /// Moves the head one step, each knot then following the previous one.
+    /// Returns the new tail position.
+    pub fn step(&mut self, direction: Direction) -> Position {
+        self.knots[0] = self.knots[0].move_to(direction);
+        for i in 1..self.knots.len() {
+            let knot = self.knots[i];
+            let moved = knot.move_delta(self.knots[i - 1] - knot);
+            // The knots behind can't move either.
+            if moved == knot {
+                break;
+            }
+            self.knots[i] = moved;
+        }
+        self.tail()
+    }
+
+    /// Moves a rope of `len` knots along `directions` and counts the
+    /// distinct positions of its tail, the starting one included.
+    pub fn count_tail_positions(
+        len: usize,
+        directions: impl IntoIterator<Item = Direction>,
+    ) -> usize {
+        let mut rope = Self::new(len);
+        let mut visited = VisitedCells::from_iter([rope.tail()]);
+        for direction in directions {
+            visited.insert(rope.step(direction));
+        }
+        visited.len()
+    }
+}
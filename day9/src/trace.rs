@@ -0,0 +1,208 @@
+use std::io::{self, Read, Write};
+
+use thiserror::Error;
+
+use crate::{Direction, Position, Rope, VisitedCells};
+
+/// The first bytes of every trace file.
+pub const TRACE_MAGIC: &[u8; 4] = b"D9TR";
+const TRACE_VERSION: u8 = 1;
+
+// Directions in the order of their codes.
+const DIRECTIONS: [Direction; 8] = {
+    use Direction::*;
+    [Left, Right, Up, Down, UpLeft, UpRight, DownLeft, DownRight]
+};
+
+#[derive(Error, Debug)]
+pub enum TraceError {
+    #[error("failed to read the trace")]
+    Io(#[from] io::Error),
+    #[error("not a trace file")]
+    NotATrace,
+    #[error("unsupported trace version {0}")]
+    UnsupportedVersion(u8),
+    #[error("a rope needs at least 2 knots, the trace has {0}")]
+    TooFewKnots(u16),
+    #[error("invalid direction at step {step}")]
+    InvalidDirection { step: usize },
+    #[error("invalid move of knot {knot} at step {step}")]
+    InvalidMove { step: usize, knot: usize },
+}
+
+/// The directions followed by a rope and the positions of its knots after
+/// every step, replayable without the original input.
+///
+/// In a file, a header holding the knot and step counts is followed by one
+/// record per step made of 4 bit codes, two per byte: the direction, then
+/// how far each knot but the head moved, as the head's move follows from
+/// the direction. A rope of 10 knots takes 5 bytes per step.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Trace {
+    knots: usize,
+    directions: Vec<Direction>,
+    // The knots of every state, starting with the one before any step.
+    positions: Vec<Position>,
+}
+
+impl Trace {
+    /// Moves a rope of `knots` knots along `directions`, recording it.
+    pub fn record(knots: usize, directions: impl IntoIterator<Item = Direction>) -> Self {
+        let mut rope = Rope::new(knots);
+        let mut trace = Self {
+            knots,
+            directions: Vec::new(),
+            positions: rope.knots().to_vec(),
+        };
+        for direction in directions {
+            rope.step(direction);
+            trace.directions.push(direction);
+            trace.positions.extend_from_slice(rope.knots());
+        }
+        trace
+    }
+
+    #[inline(always)]
+    pub fn knots(&self) -> usize {
+        self.knots
+    }
+
+    /// The number of steps.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.directions.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.directions.is_empty()
+    }
+
+    pub fn directions(&self) -> &[Direction] {
+        &self.directions
+    }
+
+    /// The knots after `step` steps, the head first.
+    pub fn knots_at(&self, step: usize) -> &[Position] {
+        &self.positions[step * self.knots..(step + 1) * self.knots]
+    }
+
+    /// The knots of every state, from the one before any step.
+    pub fn replay(&self) -> impl Iterator<Item = &[Position]> + '_ {
+        self.positions.chunks_exact(self.knots)
+    }
+
+    /// Counts the distinct positions of the tail, the starting one included.
+    pub fn count_tail_positions(&self) -> usize {
+        let tails = self.replay().map(|knots| knots[self.knots - 1]);
+        VisitedCells::from_iter(tails).len()
+    }
+
+    /// Whether `bytes` starts like a trace file.
+    pub fn is_trace(bytes: &[u8]) -> bool {
+        bytes.starts_with(TRACE_MAGIC)
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let knots = u16::try_from(self.knots)
+            .map_err(|_| io::Error::other("too many knots for a trace"))?;
+        let steps = u32::try_from(self.len())
+            .map_err(|_| io::Error::other("too many steps for a trace"))?;
+        writer.write_all(TRACE_MAGIC)?;
+        writer.write_all(&[TRACE_VERSION])?;
+        writer.write_all(&knots.to_le_bytes())?;
+        writer.write_all(&steps.to_le_bytes())?;
+
+        let mut record = vec![0; Self::record_len(self.knots)];
+        let states = self.positions.windows(2 * self.knots).step_by(self.knots);
+        for (&direction, states) in self.directions.iter().zip(states) {
+            let (before, after) = states.split_at(self.knots);
+            record.fill(0);
+            let code = DIRECTIONS.iter().position(|&d| d == direction);
+            set_nibble(
+                &mut record,
+                0,
+                code.expect("all directions have a code") as u8,
+            );
+            for knot in 1..self.knots {
+                let delta = after[knot] - before[knot];
+                let code = (delta.x() + 1) * 3 + delta.y() + 1;
+                set_nibble(&mut record, knot, code as u8);
+            }
+            writer.write_all(&record)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from(mut reader: impl Read) -> Result<Self, TraceError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if !Self::is_trace(&magic) {
+            return Err(TraceError::NotATrace);
+        }
+        let mut header = [0; 7];
+        reader.read_exact(&mut header)?;
+        if header[0] != TRACE_VERSION {
+            return Err(TraceError::UnsupportedVersion(header[0]));
+        }
+        let knots = u16::from_le_bytes([header[1], header[2]]);
+        if knots < 2 {
+            return Err(TraceError::TooFewKnots(knots));
+        }
+        let knots = usize::from(knots);
+        let steps = u32::from_le_bytes([header[3], header[4], header[5], header[6]]) as usize;
+
+        let mut trace = Self {
+            knots,
+            directions: Vec::new(),
+            positions: vec![Position::default(); knots],
+        };
+        let mut record = vec![0; Self::record_len(knots)];
+        let mut current = trace.positions.clone();
+        for step in 1..=steps {
+            reader.read_exact(&mut record)?;
+            let direction = DIRECTIONS
+                .get(usize::from(nibble(&record, 0)))
+                .copied()
+                .ok_or(TraceError::InvalidDirection { step })?;
+            let delta = Position::default().move_to(direction);
+            current[0] =
+                Self::apply(current[0], delta).ok_or(TraceError::InvalidMove { step, knot: 0 })?;
+            for (knot, pos) in current.iter_mut().enumerate().skip(1) {
+                let invalid = TraceError::InvalidMove { step, knot };
+                let code = nibble(&record, knot) as i16;
+                if code >= 9 {
+                    return Err(invalid);
+                }
+                let delta = Position::new(code / 3 - 1, code % 3 - 1);
+                *pos = Self::apply(*pos, delta).ok_or(invalid)?;
+            }
+            trace.directions.push(direction);
+            trace.positions.extend_from_slice(&current);
+        }
+        Ok(trace)
+    }
+
+    // Bytes of a step record: a code for the direction and one per knot
+    // after the head.
+    fn record_len(knots: usize) -> usize {
+        knots.div_ceil(2)
+    }
+
+    // `pos` moved by `delta`, if still in the range of the coordinates.
+    fn apply(pos: Position, delta: Position) -> Option<Position> {
+        Some(Position::new(
+            pos.x().checked_add(delta.x())?,
+            pos.y().checked_add(delta.y())?,
+        ))
+    }
+}
+
+// Codes are stored low half of the byte first.
+fn nibble(record: &[u8], i: usize) -> u8 {
+    (record[i / 2] >> (4 * (i % 2))) & 0xf
+}
+
+fn set_nibble(record: &mut [u8], i: usize, code: u8) {
+    record[i / 2] |= code << (4 * (i % 2));
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+
+use crate::Position;
+
+/// The set of cells visited by a knot, stored as one bit per cell in square
+/// tiles of 64 by 64 cells, allocated as cells get visited in them.
+///
+/// Only the tiles holding a visited cell take memory, however far apart
+/// they are, and the last tile inserted into is looked up first as knots
+/// mostly move within a tile.
+#[derive(Clone, Default, Debug)]
+pub struct VisitedCells {
+    // One word per row of cells of a tile.
+    tiles: Vec<[u64; Self::TILE]>,
+    // Index of every tile in `tiles`, by row and column of tiles.
+    index: BTreeMap<(i32, i32), usize>,
+    last: Option<((i32, i32), usize)>,
+    len: usize,
+}
+
+impl VisitedCells {
+    const TILE: usize = u64::BITS as usize;
+    const TILE_SHIFT: u32 = Self::TILE.trailing_zeros();
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks `pos` as visited, returning whether it was not already.
+    #[inline(always)]
+    pub fn insert(&mut self, pos: Position) -> bool {
+        let (key, row, mask) = Self::locate(pos);
+        let tile = match self.last {
+            Some((last, tile)) if last == key => tile,
+            _ => self.tile_or_insert(key),
+        };
+        let word = &mut self.tiles[tile][row];
+        let added = *word & mask == 0;
+        *word |= mask;
+        self.len += usize::from(added);
+        added
+    }
+
+    /// Unmarks `pos`, returning whether it was visited.
+    pub fn remove(&mut self, pos: Position) -> bool {
+        let (key, row, mask) = Self::locate(pos);
+        let Some(&tile) = self.index.get(&key) else {
+            return false;
+        };
+        let word = &mut self.tiles[tile][row];
+        let removed = *word & mask != 0;
+        *word &= !mask;
+        self.len -= usize::from(removed);
+        removed
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, pos: Position) -> bool {
+        let (key, row, mask) = Self::locate(pos);
+        let tile = match self.last {
+            Some((last, tile)) if last == key => Some(tile),
+            _ => self.index.get(&key).copied(),
+        };
+        tile.is_some_and(|tile| self.tiles[tile][row] & mask != 0)
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over the visited cells, tile after tile from the lowest `y`,
+    /// and row after row within a tile.
+    pub fn iter(&self) -> impl Iterator<Item = Position> + '_ {
+        self.index
+            .iter()
+            .flat_map(move |(&(tile_y, tile_x), &tile)| {
+                self.tiles[tile]
+                    .iter()
+                    .enumerate()
+                    .flat_map(move |(row, &word)| {
+                        let mut word = word;
+                        let x = tile_x << Self::TILE_SHIFT;
+                        let y = (tile_y << Self::TILE_SHIFT) + row as i32;
+                        std::iter::from_fn(move || {
+                            (word != 0).then(|| {
+                                let bit = word.trailing_zeros() as i32;
+                                word &= word - 1;
+                                Position::new((x + bit) as i16, y as i16)
+                            })
+                        })
+                    })
+            })
+    }
+
+    // The tile holding `pos`, the row of `pos` in it and its bit in the row.
+    #[inline(always)]
+    fn locate(pos: Position) -> ((i32, i32), usize, u64) {
+        let (x, y) = (i32::from(pos.x()), i32::from(pos.y()));
+        let key = (y >> Self::TILE_SHIFT, x >> Self::TILE_SHIFT);
+        let mask = 1 << (x & (Self::TILE as i32 - 1));
+        (key, (y & (Self::TILE as i32 - 1)) as usize, mask)
+    }
+
+    #[cold]
+    fn tile_or_insert(&mut self, key: (i32, i32)) -> usize {
+        let tiles = &mut self.tiles;
+        let tile = *self.index.entry(key).or_insert_with(|| {
+            tiles.push([0; Self::TILE]);
+            tiles.len() - 1
+        });
+        self.last = Some((key, tile));
+        tile
+    }
+}
+
+impl Extend<Position> for VisitedCells {
+    fn extend<T: IntoIterator<Item = Position>>(&mut self, iter: T) {
+        for pos in iter {
+            self.insert(pos);
+        }
+    }
+}
+
+impl FromIterator<Position> for VisitedCells {
+    fn from_iter<T: IntoIterator<Item = Position>>(iter: T) -> Self {
+        let mut visited = Self::new();
+        visited.extend(iter);
+        visited
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::Direction;
+
+    #[test]
+    fn test_visited_cells() {
+        let mut visited = VisitedCells::new();
+        assert!(visited.is_empty() && !visited.contains(Position::default()));
+        let mut expected = HashSet::new();
+        // A spiral growing on every side, then the corners of the space.
+        let mut pos = Position::default();
+        for (i, direction) in [
+            Direction::Right,
+            Direction::Up,
+            Direction::Left,
+            Direction::Down,
+        ]
+        .into_iter()
+        .cycle()
+        .take(80)
+        .enumerate()
+        {
+            for _ in 0..i * 3 {
+                pos = pos.move_to(direction);
+                assert_eq!(visited.insert(pos), expected.insert(pos));
+            }
+        }
+        // The ends of the coordinates, without covering the whole space.
+        for (x, y) in [(i16::MIN, 5), (i16::MAX, -7), (i16::MAX, 0)] {
+            let pos = Position::new(x, y);
+            assert!(visited.insert(pos) && expected.insert(pos));
+            assert!(!visited.insert(pos));
+        }
+        assert_eq!(visited.len(), expected.len());
+        assert!(expected.iter().all(|&pos| visited.contains(pos)));
+        assert_eq!(visited.iter().collect::<HashSet<_>>(), expected);
+        assert!(!visited.contains(Position::new(i16::MAX, 1)));
+        assert!(
+            visited.remove(Position::new(i16::MAX, 0))
+                && !visited.remove(Position::new(i16::MAX, 0))
+        );
+        assert_eq!(visited.len(), expected.len() - 1);
+        let column = [Position::new(3, i16::MAX), Position::new(3, i16::MIN)];
+        let visited = VisitedCells::from_iter(column);
+        assert_eq!(visited.iter().collect::<Vec<_>>(), [column[1], column[0]]);
+    }
+
+    #[test]
+    fn test_sparse_cells() {
+        // Far apart cells only take the tiles holding them.
+        let corners = [(i16::MIN, i16::MIN), (i16::MAX, i16::MAX), (0, i16::MIN)];
+        let visited = VisitedCells::from_iter(corners.map(|(x, y)| Position::new(x, y)));
+        assert_eq!((visited.len(), visited.tiles.len()), (3, 3));
+        assert!(!visited.contains(Position::new(1, i16::MIN)));
+        // A long run takes a tile per 64 cells.
+        let mut visited = VisitedCells::new();
+        visited.extend((-1000..1000).map(|x| Position::new(x * 16, 7)));
+        assert_eq!((visited.len(), visited.tiles.len()), (2000, 500));
+        assert!(visited.iter().all(|pos| pos.y() == 7 && pos.x() % 16 == 0));
+    }
+}